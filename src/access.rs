@@ -0,0 +1,65 @@
+use crate::utils;
+use crate::utils::clock;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Accesses remembered for each paper.
+const MAX_ACCESSES: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Action {
+    Open,
+    Yank,
+}
+
+/// A paper being opened or its bibtex copied.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Access {
+    pub action: Action,
+    pub at: u64,
+}
+
+/// Accesses of each paper, by paper id, latest last.
+pub type Accesses = BTreeMap<String, Vec<Access>>;
+
+/// Remembers that the paper was used now. Nothing is recorded in a
+/// read-only session.
+pub fn record(id: &str, action: Action) -> Result<()> {
+    if utils::io::read_only() {
+        return Ok(());
+    }
+    let at = clock::now()?;
+    let mut accesses = load_accesses()?;
+    let log = accesses.entry(id.to_string()).or_default();
+    log.push(Access { action, at });
+    if log.len() > MAX_ACCESSES {
+        log.remove(0);
+    }
+    save_accesses(&accesses)
+}
+
+/// When the paper was last opened or yanked.
+pub fn last_access(accesses: &Accesses, id: &str) -> Option<u64> {
+    accesses
+        .get(id)
+        .and_then(|log| log.last())
+        .map(|access| access.at)
+}
+
+pub fn save_accesses(accesses: &Accesses) -> Result<()> {
+    let encoded: Vec<u8> = serialize(accesses)?;
+    let filename = utils::io::accesses_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_accesses() -> Result<Accesses> {
+    let filename = utils::io::accesses_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Accesses = deserialize(&buffer)?;
+    Ok(decoded)
+}
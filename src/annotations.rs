@@ -0,0 +1,83 @@
+use crate::claims::Evidence;
+use crate::utils;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SharedQuote {
+    pub topic: String,
+    pub text: String,
+    pub page: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SharedClaim {
+    pub text: String,
+    pub evidence: Evidence,
+    pub page: usize,
+}
+
+/// What one reader noted about a paper: custom fields, quotes and claims.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Notes {
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub quotes: Vec<SharedQuote>,
+    #[serde(default)]
+    pub claims: Vec<SharedClaim>,
+}
+
+impl Notes {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.quotes.is_empty() && self.claims.is_empty()
+    }
+}
+
+/// A paper in a bundle, identified by doi or title as keys differ between
+/// libraries.
+#[derive(Serialize, Deserialize)]
+pub struct BundlePaper {
+    pub key: String,
+    pub doi: Option<String>,
+    pub title: String,
+    #[serde(flatten)]
+    pub notes: Notes,
+}
+
+/// The json file readers pass around.
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    pub author: String,
+    pub created: u64,
+    pub papers: Vec<BundlePaper>,
+}
+
+/// Someone else's notes on a paper, kept apart from the library's own.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Layer {
+    pub author: String,
+    pub imported: u64,
+    pub notes: Notes,
+}
+
+/// Imported layers of each paper, by paper id.
+pub type Annotations = BTreeMap<String, Vec<Layer>>;
+
+pub fn save_annotations(annotations: &Annotations) -> Result<()> {
+    let encoded: Vec<u8> = serialize(annotations)?;
+    let filename = utils::io::annotations_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_annotations() -> Result<Annotations> {
+    let filename = utils::io::annotations_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Annotations = deserialize(&buffer)?;
+    Ok(decoded)
+}
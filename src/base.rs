@@ -1,13 +1,21 @@
+use crate::parser::bibfile::{entry_kind, extract_entry, normalize_doi};
 use crate::stacks::Stack;
-use crate::utils;
-use anyhow::{anyhow, Result};
-use bincode;
+use crate::utils::fmt::{self, paint, reset, Ellipsis, Tone};
+use crate::{blog, utils};
+use anyhow::{anyhow, bail, Result};
 use indexmap::IndexMap;
 use open;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{Read, Write};
-use termion::color;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SHARED_MARKER: &str = "[shared]";
+/// Longest author column before it gets cut.
+const MAX_AUTHOR: usize = 24;
 
 #[derive(Clone, Debug, Serialize, Deserialize)] // TODO: Why do we need this clone?
 pub struct Paper {
@@ -15,64 +23,376 @@ pub struct Paper {
     pub author: String,
     pub year: i64,
     pub title: String,
+    /// Bibtex entry type, e.g. article or phdthesis
+    pub kind: String,
     pub stack: Vec<Stack>,
     pub bibtex: String,
+    /// Set for papers read from the shared library, never written to disk
+    #[serde(skip)]
+    pub shared: bool,
+}
+
+/// Layout of papers before they recorded their entry type (format version 1 and older).
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct PaperV1 {
+    id: String,
+    author: String,
+    year: i64,
+    title: String,
+    stack: Vec<Stack>,
+    bibtex: String,
+}
+
+impl From<PaperV1> for Paper {
+    fn from(old: PaperV1) -> Self {
+        let kind = extract_entry(&old.bibtex)
+            .map(|entry| entry_kind(&entry))
+            .unwrap_or_else(|_| "misc".to_string());
+        Paper {
+            id: old.id,
+            author: old.author,
+            year: old.year,
+            title: old.title,
+            kind,
+            stack: old.stack,
+            bibtex: old.bibtex,
+            shared: false,
+        }
+    }
+}
+
+fn upgrade(papers: IndexMap<String, PaperV1>) -> IndexMap<String, Paper> {
+    papers
+        .into_iter()
+        .map(|(key, paper)| (key, paper.into()))
+        .collect()
 }
 
 impl Paper {
+    /// Tag for entry types that would otherwise look like articles.
+    fn kind_label(&self) -> Option<&'static str> {
+        match self.kind.as_str() {
+            "phdthesis" => Some("(phd thesis)"),
+            "mastersthesis" => Some("(msc thesis)"),
+            "techreport" => Some("(report)"),
+            "book" | "booklet" => Some("(book)"),
+            "inbook" | "incollection" => Some("(chapter)"),
+            _ => None,
+        }
+    }
+    pub fn pdf_location(&self) -> Result<PathBuf> {
+        if self.shared {
+            return utils::io::shared_pdf_path(&self.id);
+        }
+        match linked_pdf(&self.id)? {
+            Some(linked) => Ok(linked),
+            None => utils::io::pdf_path(&self.id),
+        }
+    }
+    /// Doi link if there is one, otherwise the url or arXiv page.
+    pub fn link(&self) -> Option<String> {
+        if let Some(doi) = self.doi() {
+            return Some(format!("https://doi.org/{}", doi));
+        }
+        self.bibtex_field("url").or_else(|| {
+            self.bibtex_field("eprint")
+                .map(|id| format!("https://arxiv.org/abs/{}", id))
+        })
+    }
     pub fn open_pdf(&self) -> Result<()> {
-        let pdf_path = utils::io::pdf_path(&self.id)?;
+        let pdf_path = self.pdf_location()?;
         open::that(pdf_path).map_err(|err| anyhow!("Could not open pdf: {}", err))
     }
     fn get_slack(&self) -> usize {
+        let marker = if self.shared {
+            SHARED_MARKER.len() + 1
+        } else {
+            0
+        };
+        let label = self.kind_label().map_or(0, |label| label.len() + 1);
         self.stack
             .iter()
-            .fold(0, |acc, stack| acc + stack.name.len() + 3)
+            .fold(marker + label, |acc, stack| acc + stack.name.len() + 3)
     }
-    fn trim_title(&self, max_length: u16) -> String {
-        let mut length = max_length as usize;
-        length -= 4 + 2;
-        length -= self.author.len() + 4;
-        length -= self.get_slack();
-        fit_string_to_length(&self.title, length)
+    /// Room left for the title once year, author and stacks are in.
+    fn title_room(&self, max_length: u16, author: &str) -> usize {
+        (max_length as usize)
+            .saturating_sub(4 + 2)
+            .saturating_sub(author.chars().count() + 4)
+            .saturating_sub(self.get_slack())
+    }
+    /// Title cut to fit, plus what goes on a second line when wrapping.
+    fn trim_title(&self, room: usize) -> (String, Option<String>) {
+        if self.title.chars().count() <= room {
+            return (self.title.clone(), None);
+        }
+        if fmt::wrap() {
+            let (first, rest) = split_at_word(&self.title, room);
+            return (first, Some(fit_string_to_length(&rest, room)));
+        }
+        match fmt::ellipsis() {
+            Ellipsis::End => (fit_string_to_length(&self.title, room), None),
+            Ellipsis::Middle => (fit_string_middle(&self.title, room), None),
+        }
     }
     pub fn display(&self, max_width: u16) -> String {
-        // let slack = self.get_slack();
+        // The year and author columns always stay visible
+        let author = fit_string_to_length(&self.author, MAX_AUTHOR);
+        let (title, rest) = self.trim_title(self.title_room(max_width, &author));
         let mut display_string = format!(
             "{} {}|{} {} {}|{} {}",
             self.year,
-            color::Fg(color::Rgb(83, 110, 122)),
-            color::Fg(color::Reset),
-            self.author,
-            color::Fg(color::Rgb(83, 110, 122)),
-            color::Fg(color::Reset),
-            self.trim_title(max_width),
+            paint(Tone::Muted),
+            reset(),
+            author,
+            paint(Tone::Muted),
+            reset(),
+            title,
         );
-        // display_string = fit_string_to_length(display_string, max_width - slack);
+        if let Some(label) = self.kind_label() {
+            display_string.push_str(&format!(" {}{}{}", paint(Tone::Muted), label, reset()));
+        }
         for stack in self.stack.iter() {
             display_string.push_str(&format!(" {}", stack));
         }
+        if self.shared {
+            display_string.push_str(&format!(
+                " {}{}{}",
+                paint(Tone::Muted),
+                SHARED_MARKER,
+                reset()
+            ));
+        }
+        if let Some(rest) = rest {
+            let indent = self.year.to_string().len() + author.chars().count() + 6;
+            display_string.push_str(&format!("\n{:indent$}{}", "", rest, indent = indent));
+        }
         display_string
     }
 }
 
-fn fit_string_to_length(input: &str, max_length: usize) -> String {
-    if input.len() <= max_length {
+/// Earliest year a paper can have.
+const MIN_YEAR: i64 = 1000;
+/// Longer titles are abstracts or whole citations pasted in the wrong field.
+const MAX_TITLE: usize = 500;
+
+/// Why a paper was refused before it could reach the library.
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    MissingKey,
+    /// Keys cannot hold whitespace or any of , { } " # % ~ \
+    InvalidKey {
+        key: String,
+        character: char,
+    },
+    MissingTitle,
+    TitleTooLong {
+        length: usize,
+    },
+    MissingAuthor,
+    YearOutOfRange {
+        year: i64,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::MissingKey => write!(f, "The entry has no key"),
+            ValidationError::InvalidKey { key, character } => {
+                write!(f, "Key {} cannot contain '{}'", key, character)
+            }
+            ValidationError::MissingTitle => write!(f, "The entry has no title"),
+            ValidationError::TitleTooLong { length } => write!(
+                f,
+                "Title is {} characters long, more than the {} a title can have",
+                length, MAX_TITLE
+            ),
+            ValidationError::MissingAuthor => write!(f, "The entry has no author"),
+            ValidationError::YearOutOfRange { year } => write!(f, "Year {} is not plausible", year),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Builds papers from any source, checking them the same way so none can
+/// bring in a row that breaks display or export later.
+#[derive(Default)]
+pub struct PaperBuilder {
+    id: String,
+    author: String,
+    year: i64,
+    title: String,
+    kind: Option<String>,
+    bibtex: String,
+}
+
+impl PaperBuilder {
+    pub fn new(id: &str) -> Self {
+        PaperBuilder {
+            id: id.trim().to_string(),
+            ..Default::default()
+        }
+    }
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = author.trim().to_string();
+        self
+    }
+    pub fn year(mut self, year: i64) -> Self {
+        self.year = year;
+        self
+    }
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.trim().to_string();
+        self
+    }
+    /// Bibtex entry type, misc when not given.
+    pub fn kind(mut self, kind: &str) -> Self {
+        self.kind = Some(kind.to_lowercase());
+        self
+    }
+    pub fn bibtex(mut self, bibtex: &str) -> Self {
+        self.bibtex = bibtex.to_string();
+        self
+    }
+
+    pub fn build(self) -> std::result::Result<Paper, ValidationError> {
+        if self.id.is_empty() {
+            return Err(ValidationError::MissingKey);
+        }
+        // Only what breaks a bibtex key, as sources like ADS use & and accents
+        let breaks = |c: char| c.is_whitespace() || ",{}\"#%~\\".contains(c);
+        if let Some(character) = self.id.chars().find(|c| breaks(*c)) {
+            return Err(ValidationError::InvalidKey {
+                key: self.id,
+                character,
+            });
+        }
+        if self.title.is_empty() {
+            return Err(ValidationError::MissingTitle);
+        }
+        let length = self.title.chars().count();
+        if length > MAX_TITLE {
+            return Err(ValidationError::TitleTooLong { length });
+        }
+        if self.author.is_empty() {
+            return Err(ValidationError::MissingAuthor);
+        }
+        // Forthcoming papers can be dated next year. Plausibility is judged
+        // against the real date, even when stored timestamps are pinned.
+        let this_year = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|elapsed| fmt::date(elapsed.as_secs())[..4].parse::<i64>().ok())
+            .unwrap_or(i64::MAX - 1);
+        if !(MIN_YEAR..=this_year + 1).contains(&self.year) {
+            return Err(ValidationError::YearOutOfRange { year: self.year });
+        }
+        Ok(self.build_unchecked())
+    }
+
+    /// Builds a paper already in the library without judging it again, so
+    /// entries stored before a check existed can still be read and rewritten.
+    pub fn build_unchecked(self) -> Paper {
+        Paper {
+            id: self.id,
+            author: self.author,
+            year: self.year,
+            title: self.title,
+            kind: self.kind.unwrap_or_else(|| "misc".to_string()),
+            stack: Vec::new(),
+            bibtex: self.bibtex,
+            shared: false,
+        }
+    }
+}
+
+/// Looks a paper up by key, doi or doi alias.
+pub fn find_paper<'a>(papers: &'a IndexMap<String, Paper>, key: &str) -> Option<&'a Paper> {
+    if let Some(paper) = papers.get(key) {
+        return Some(paper);
+    }
+    let key = key.trim().to_lowercase();
+    if key.starts_with("doi-") {
+        return papers
+            .values()
+            .find(|paper| paper.alias().as_deref() == Some(key.as_str()));
+    }
+    let doi = normalize_doi(&key);
+    papers
+        .values()
+        .find(|paper| paper.doi().as_deref() == Some(doi.as_str()))
+}
+
+pub fn fit_string_to_length(input: &str, max_length: usize) -> String {
+    if input.chars().count() <= max_length {
         return String::from(input);
     }
 
-    let mut result = String::with_capacity(max_length);
-    result.push_str(&input[..max_length - 3]);
+    let mut result: String = input.chars().take(max_length.saturating_sub(3)).collect();
     result.push_str("...");
     result
 }
 
+/// Cuts the middle out of long strings, where titles are least distinctive.
+fn fit_string_middle(input: &str, max_length: usize) -> String {
+    let length = input.chars().count();
+    if length <= max_length {
+        return String::from(input);
+    }
+    let tail = max_length.saturating_sub(3) / 2;
+    let head = max_length.saturating_sub(3) - tail;
+    let mut result: String = input.chars().take(head).collect();
+    result.push_str("...");
+    result.extend(input.chars().skip(length - tail));
+    result
+}
+
+/// Splits at the last space that keeps the first part within `max_length`.
+fn split_at_word(input: &str, max_length: usize) -> (String, String) {
+    let first: String = input.chars().take(max_length).collect();
+    let cut = match first.rfind(' ') {
+        Some(index) if index > 0 => index,
+        _ => first.len(),
+    };
+    (
+        input[..cut].trim_end().to_string(),
+        input[cut..].trim_start().to_string(),
+    )
+}
+
+/// Papers files start with this tag followed by a format version.
+/// Files without it were written by bib 0.3 and older.
+const MAGIC: &[u8; 4] = b"BIB\0";
+const VERSION: u32 = 2;
+
 pub fn save_papers(papers: &IndexMap<String, Paper>) -> Result<()> {
-    let encoded: Vec<u8> = bincode::serialize(papers)?;
+    let mut encoded: Vec<u8> = MAGIC.to_vec();
+    encoded.extend_from_slice(&VERSION.to_le_bytes());
+    encoded.extend(bincode::serialize(papers)?);
     let filename = utils::io::papers_path()?;
-    let mut file = File::create(filename)?;
-    file.write_all(&encoded)?;
-    Ok(())
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+/// Decodes a papers file, `None` means it predates the versioned format.
+fn decode_papers(buffer: &[u8]) -> Result<Option<IndexMap<String, Paper>>> {
+    let payload = match buffer.strip_prefix(MAGIC) {
+        Some(payload) if payload.len() >= 4 => payload,
+        Some(_) => bail!("Papers file is truncated"),
+        None => return Ok(None),
+    };
+    let (version, payload) = payload.split_at(4);
+    let version = u32::from_le_bytes(version.try_into()?);
+    match version {
+        1 => Ok(Some(upgrade(bincode::deserialize(payload)?))),
+        VERSION => Ok(Some(bincode::deserialize(payload)?)),
+        _ => bail!(
+            "Papers file has format version {}, upgrade bib to read it",
+            version
+        ),
+    }
 }
 
 pub fn load_papers() -> Result<IndexMap<String, Paper>> {
@@ -80,9 +400,201 @@ pub fn load_papers() -> Result<IndexMap<String, Paper>> {
     if !filename.exists() {
         return Ok(IndexMap::new());
     }
-    let mut file = File::open(filename)?;
+    let mut file = File::open(&filename)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
-    let decoded: IndexMap<String, Paper> = bincode::deserialize(&buffer)?;
-    Ok(decoded)
+    match decode_papers(&buffer)? {
+        Some(papers) => Ok(papers),
+        None => migrate_legacy(&filename, &buffer, utils::io::read_only()),
+    }
+}
+
+/// Papers of the shared library. It is never written to, so old formats are read as they are.
+pub fn load_shared_papers() -> Result<IndexMap<String, Paper>> {
+    let filename = match utils::io::shared_path("papers.bin")? {
+        Some(filename) if filename.exists() => filename,
+        _ => return Ok(IndexMap::new()),
+    };
+    let buffer = fs::read(&filename)?;
+    let mut papers = match decode_papers(&buffer)? {
+        Some(papers) => papers,
+        None => upgrade(bincode::deserialize(&buffer)?),
+    };
+    for paper in papers.values_mut() {
+        // Stacks of the shared library mean nothing here
+        paper.stack.clear();
+        paper.shared = true;
+    }
+    Ok(papers)
+}
+
+/// Rewrites a papers file from before the format was versioned,
+/// keeping the original next to it in case anything goes wrong. A read-only
+/// library is upgraded in memory only, so it can still be browsed.
+fn migrate_legacy(
+    filename: &Path,
+    buffer: &[u8],
+    read_only: bool,
+) -> Result<IndexMap<String, Paper>> {
+    let papers: IndexMap<String, PaperV1> = bincode::deserialize(buffer)
+        .map_err(|err| anyhow!("Could not read papers file: {}", err))?;
+    let papers = upgrade(papers);
+    if read_only {
+        return Ok(papers);
+    }
+    let backup = filename.with_extension("bin.bak");
+    fs::copy(filename, &backup)?;
+    save_papers(&papers)?;
+    blog!(
+        "Migrated",
+        "{} papers, old library kept at {}",
+        papers.len(),
+        backup.display()
+    );
+    Ok(papers)
+}
+
+/// Pdfs left where they were when added, by paper id.
+pub type Links = BTreeMap<String, PathBuf>;
+
+/// Links as last read, so locating many pdfs reads the file once.
+static LINKS: Mutex<Option<Links>> = Mutex::new(None);
+
+fn linked_pdf(id: &str) -> Result<Option<PathBuf>> {
+    let mut cached = LINKS.lock().unwrap();
+    if cached.is_none() {
+        *cached = Some(load_links()?);
+    }
+    Ok(cached.as_ref().and_then(|links| links.get(id).cloned()))
+}
+
+pub fn save_links(links: &Links) -> Result<()> {
+    let encoded: Vec<u8> = bincode::serialize(links)?;
+    let filename = utils::io::links_path()?;
+    utils::io::write_atomic(&filename, &encoded)?;
+    *LINKS.lock().unwrap() = Some(links.clone());
+    Ok(())
+}
+
+pub fn load_links() -> Result<Links> {
+    let filename = utils::io::links_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = fs::read(filename)?;
+    Ok(bincode::deserialize(&buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid(id: &str) -> PaperBuilder {
+        PaperBuilder::new(id)
+            .author("Doe, Jane")
+            .year(2020)
+            .title("A Study of Things")
+    }
+
+    #[test]
+    fn builds_a_valid_paper() {
+        let paper = valid("doe2020").kind("Article").build().unwrap();
+        assert_eq!(paper.id, "doe2020");
+        assert_eq!(paper.kind, "article");
+        assert_eq!(valid("doe2020").build().unwrap().kind, "misc");
+    }
+
+    #[test]
+    fn rejects_bad_keys() {
+        assert_eq!(
+            valid("  ").build().unwrap_err(),
+            ValidationError::MissingKey
+        );
+        assert_eq!(
+            valid("doe 2020").build().unwrap_err(),
+            ValidationError::InvalidKey {
+                key: "doe 2020".to_string(),
+                character: ' '
+            }
+        );
+        assert_eq!(
+            valid("doe{2020").build().unwrap_err(),
+            ValidationError::InvalidKey {
+                key: "doe{2020".to_string(),
+                character: '{'
+            }
+        );
+        assert!(valid("arXiv:2301.00001v2/a_b+c-d").build().is_ok());
+        assert!(valid("2019A&A...625A..10X").build().is_ok());
+        assert!(valid("Müller2020").build().is_ok());
+        for key in ["doe,2020", "doe%2020", "doe#2020", "doe~2020", "doe\\2020"] {
+            assert!(valid(key).build().is_err(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_overlong_titles() {
+        assert_eq!(
+            valid("doe2020").title(" ").build().unwrap_err(),
+            ValidationError::MissingTitle
+        );
+        let long = "x".repeat(MAX_TITLE + 1);
+        assert_eq!(
+            valid("doe2020").title(&long).build().unwrap_err(),
+            ValidationError::TitleTooLong {
+                length: MAX_TITLE + 1
+            }
+        );
+        assert!(valid("doe2020").title(&long[1..]).build().is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_authors() {
+        assert_eq!(
+            valid("doe2020").author("").build().unwrap_err(),
+            ValidationError::MissingAuthor
+        );
+    }
+
+    #[test]
+    fn checks_year_bounds() {
+        let this_year: i64 = fmt::date(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        )[..4]
+            .parse()
+            .unwrap();
+        assert!(valid("a").year(MIN_YEAR).build().is_ok());
+        assert!(valid("a").year(this_year + 1).build().is_ok());
+        for year in [MIN_YEAR - 1, this_year + 2, 0] {
+            assert_eq!(
+                valid("a").year(year).build().unwrap_err(),
+                ValidationError::YearOutOfRange { year }
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_papers_load_in_memory_when_read_only() {
+        let legacy: IndexMap<String, PaperV1> = IndexMap::from([(
+            "doe2020".to_string(),
+            PaperV1 {
+                id: "doe2020".to_string(),
+                author: "Doe".to_string(),
+                year: 2020,
+                title: "A Study of Things".to_string(),
+                stack: Vec::new(),
+                bibtex: "@phdthesis{doe2020, title = {A Study of Things}, author = {Doe, Jane}, year = {2020}}".to_string(),
+            },
+        )]);
+        let buffer = bincode::serialize(&legacy).unwrap();
+        assert!(decode_papers(&buffer).unwrap().is_none());
+        // Neither read nor written, as a read-only library must not be touched
+        let filename = Path::new("/nonexistent/bib/papers.bin");
+        let papers = migrate_legacy(filename, &buffer, true).unwrap();
+        assert_eq!(papers["doe2020"].kind, "phdthesis");
+        assert!(!filename.with_extension("bin.bak").exists());
+    }
 }
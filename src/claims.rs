@@ -0,0 +1,158 @@
+use crate::utils;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What a claim rests on, judged from its wording.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Evidence {
+    /// Experiments, measurements or benchmarks
+    Empirical,
+    /// Proofs and derivations
+    Theoretical,
+    /// Asserted without saying how it is supported
+    Stated,
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Evidence::Empirical => "empirical",
+            Evidence::Theoretical => "theoretical",
+            Evidence::Stated => "stated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A sentence in which a paper claims a finding, with its embedding.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Claim {
+    pub text: String,
+    pub evidence: Evidence,
+    pub page: usize,
+    pub coords: Vec<f32>,
+}
+
+/// Claims of each paper, by paper id, in the order they appear.
+pub type Claims = BTreeMap<String, Vec<Claim>>;
+
+/// Phrases with which papers announce what they found.
+const CUES: [&str; 17] = [
+    "we show",
+    "we demonstrate",
+    "we find",
+    "we found",
+    "we prove",
+    "we observe",
+    "we establish",
+    "our results",
+    "results show",
+    "results suggest",
+    "results indicate",
+    "this shows",
+    "this suggests",
+    "outperform",
+    "significantly",
+    "leads to",
+    "is equivalent to",
+];
+const THEORETICAL: [&str; 7] = [
+    "prove",
+    "theorem",
+    "lemma",
+    "bound",
+    "converge",
+    "derive",
+    "equivalent",
+];
+const EMPIRICAL: [&str; 10] = [
+    "experiment",
+    "outperform",
+    "accuracy",
+    "benchmark",
+    "dataset",
+    "baseline",
+    "measure",
+    "observe",
+    "significantly",
+    "%",
+];
+
+/// Evidence of the sentence if it reads like a claim.
+pub fn classify(sentence: &str) -> Option<Evidence> {
+    let lower = sentence.to_lowercase();
+    if !CUES.iter().any(|cue| lower.contains(cue)) {
+        return None;
+    }
+    if THEORETICAL.iter().any(|word| lower.contains(word)) {
+        Some(Evidence::Theoretical)
+    } else if EMPIRICAL.iter().any(|word| lower.contains(word)) {
+        Some(Evidence::Empirical)
+    } else {
+        Some(Evidence::Stated)
+    }
+}
+
+pub fn save_claims(claims: &Claims) -> Result<()> {
+    let encoded: Vec<u8> = serialize(claims)?;
+    let filename = utils::io::claims_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_claims() -> Result<Claims> {
+    let filename = utils::io::claims_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Claims = deserialize(&buffer)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_empirical_claims() {
+        assert_eq!(
+            classify("We show that our model outperforms every baseline on ImageNet."),
+            Some(Evidence::Empirical)
+        );
+        assert_eq!(
+            classify("Our results indicate a 12% drop in error."),
+            Some(Evidence::Empirical)
+        );
+    }
+
+    #[test]
+    fn classifies_theoretical_claims() {
+        assert_eq!(
+            classify("We prove that gradient descent converges in linear time."),
+            Some(Evidence::Theoretical)
+        );
+        // Theoretical wording wins over empirical words in the same sentence
+        assert_eq!(
+            classify("We establish a lower bound that matches the benchmark accuracy."),
+            Some(Evidence::Theoretical)
+        );
+    }
+
+    #[test]
+    fn classifies_stated_claims() {
+        assert_eq!(
+            classify("This suggests that attention is all you need."),
+            Some(Evidence::Stated)
+        );
+    }
+
+    #[test]
+    fn ignores_sentences_without_cues() {
+        assert_eq!(classify("The dataset contains 10,000 images."), None);
+        assert_eq!(classify("Section 3 describes the method."), None);
+    }
+}
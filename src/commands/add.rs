@@ -1,10 +1,22 @@
-use crate::base::{load_papers, save_papers, Paper};
-use crate::embedding::{load_vectors, save_vectors, Point};
+use crate::base::{load_links, load_papers, save_links, save_papers, Paper};
+use crate::embedding::{load_vectors, pdf_pages, save_vectors, Point};
+use crate::keys::unique_key;
 use crate::parser::arxiv::{self, download_arxiv_pdf, download_pdf};
+use crate::parser::bibfile::{normalize_doi, normalize_title};
+use crate::parser::doi::doi2bib;
+use crate::parser::unpaywall;
+use crate::revisions::{self, Revision, Revisions};
 use crate::stacks::Stack;
-use crate::{blog, utils};
-use anyhow::Result;
+use crate::tombstones::{find_tombstone, load_tombstones, save_tombstones};
+use crate::utils::diff;
+use crate::utils::fmt::{paint, reset, Tone};
+use crate::{blog, utils, warn};
+use anyhow::{anyhow, bail, Result};
 use indexmap::IndexMap;
+use regex::Regex;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 fn prompt_message() -> Result<String> {
@@ -38,39 +50,143 @@ fn build_paper(url: Option<String>) -> Result<Paper> {
         None => prompt_message()?,
         Some(url) => arxiv::arxiv2bib(&url)?,
     };
-    Paper::from_bibtex(&bibtex)
+    with_key_style(Paper::from_bibtex(&bibtex)?)
 }
 
-fn is_duplicate(
+/// Gives the paper a key in the configured style. A paper already in the
+/// library keeps its key, so it is still merged as a duplicate.
+fn with_key_style(paper: Paper) -> Result<Paper> {
+    let style = match utils::io::read_config_file()?.key_style {
+        Some(style) => style,
+        None => return Ok(paper),
+    };
+    let papers = load_papers()?;
+    let title = normalize_title(&paper.title);
+    let known = papers.values().find(|known| {
+        (paper.doi().is_some() && known.doi() == paper.doi())
+            || normalize_title(&known.title) == title
+    });
+    let key = match known {
+        Some(known) => known.id.clone(),
+        None => unique_key(&style.key(&paper), |key| papers.contains_key(key)),
+    };
+    Ok(paper.with_key(&key))
+}
+
+/// Merges a paper that is already in the library, asking which fields to
+/// update. Returns the rewrite to record once the papers are saved, or None
+/// when the paper is new.
+fn merge_duplicate(
     papers: &mut IndexMap<String, Paper>,
     paper: &Paper,
     current_stack: Option<Stack>,
-) -> bool {
-    match papers.get_mut(&paper.id) {
-        None => false,
-        Some(dupe) => {
-            if let Some(stack) = current_stack {
-                if !dupe.stack.contains(&stack) {
-                    dupe.stack.push(stack);
-                }
-            }
-            true
+    interactive: bool,
+) -> Result<Option<Revisions>> {
+    let dupe = match papers.get_mut(&paper.id) {
+        None => return Ok(None),
+        Some(dupe) => dupe,
+    };
+    warn!("Duplicate", "{} is already in the library", paper.id);
+    let bibtex = match interactive {
+        true => diff::resolve(&dupe.bibtex, &paper.bibtex)?,
+        false => dupe.bibtex.clone(),
+    };
+    let mut pending = Revisions::new();
+    if bibtex != dupe.bibtex {
+        let revision = Revision::new("add", &dupe.bibtex, &bibtex)?;
+        pending.insert(dupe.id.clone(), vec![revision]);
+        let stack = std::mem::take(&mut dupe.stack);
+        *dupe = Paper {
+            stack,
+            ..Paper::from_stored_bibtex(&bibtex)?
+        };
+        blog!("Updated", "{}", dupe.title);
+    }
+    if let Some(stack) = current_stack {
+        if !dupe.stack.contains(&stack) {
+            dupe.stack.push(stack);
         }
     }
+    Ok(Some(pending))
+}
+
+/// Warns when the paper was deliberately removed before, and tells whether
+/// to skip it.
+fn tombstoned(paper: &Paper, respect_tombstones: bool) -> Result<bool> {
+    let removed = match find_tombstone(&load_tombstones()?, paper) {
+        Some(removed) => removed,
+        None => return Ok(false),
+    };
+    warn!(
+        "Removed",
+        "{} was deliberately removed before as {}", paper.id, removed
+    );
+    if respect_tombstones {
+        warn!("Skipped", "{}", paper.id);
+    }
+    Ok(respect_tombstones)
+}
+
+/// Embeds the paper and puts it on top of the library, linking its pdf when
+/// it stays where it was.
+fn store(
+    mut papers: IndexMap<String, Paper>,
+    mut paper: Paper,
+    bytes: Vec<u8>,
+    linked: Option<PathBuf>,
+    current_stack: Option<Stack>,
+) -> Result<()> {
+    if let Some(stack) = current_stack {
+        paper.stack.push(stack)
+    }
+    // Embed the dude
+    if utils::io::read_config_file()?.features.embeddings {
+        let vector = Point::from_bytes(paper.id.clone(), bytes)?;
+        let mut vectors = load_vectors()?;
+        vectors.insert(paper.id.clone(), vector);
+        save_vectors(&vectors)?;
+    } else {
+        blog!("Skipped", "embedding, turned off in config");
+    }
+    if let Some(path) = linked {
+        let mut links = load_links()?;
+        links.insert(paper.id.clone(), path);
+        save_links(&links)?;
+    }
+    //save it i
+    blog!("Saving", "{}", paper.title);
+    // Adding it back means it is wanted again
+    let mut tombstones = load_tombstones()?;
+    if let Some(removed) = find_tombstone(&tombstones, &paper) {
+        tombstones.remove(&removed);
+        save_tombstones(&tombstones)?;
+    }
+    papers.shift_insert(0, paper.id.clone(), paper);
+    save_papers(&papers)?;
+    Ok(())
 }
 
-pub fn add(url: String, pdf: bool, web: bool) -> Result<()> {
-    let mut paper: Paper;
+pub fn add(url: String, pdf: bool, web: bool, link: bool, respect_tombstones: bool) -> Result<()> {
+    utils::io::require_writable()?;
+    let paper = match pdf || web {
+        true => build_paper(None)?,
+        false => build_paper(Some(url.clone()))?,
+    };
+    if tombstoned(&paper, respect_tombstones)? {
+        return Ok(());
+    }
     let bytes: Vec<u8>;
-    if pdf {
-        paper = build_paper(None)?;
+    let mut linked = None;
+    if pdf && link {
+        let (path, contents) = utils::io::read_linked_file(&url)?;
+        linked = Some(path);
+        bytes = contents;
+    } else if pdf {
         bytes = utils::io::read_and_move_file(&url, &paper.id)?;
     } else if web {
-        paper = build_paper(None)?;
         blog!("Downloading", "pdf from url: {}", url);
         bytes = download_pdf(&url, &paper.id)?;
     } else {
-        paper = build_paper(Some(url.clone()))?;
         blog!("Downloading", "pdf from url: {}", &url);
         bytes = download_arxiv_pdf(&url, &paper.id)?;
     }
@@ -79,23 +195,263 @@ pub fn add(url: String, pdf: bool, web: bool) -> Result<()> {
     let config = utils::io::read_config_file()?;
     let mut papers = load_papers()?;
 
-    if is_duplicate(&mut papers, &paper, config.current_stack()) {
+    if let Some(pending) = merge_duplicate(&mut papers, &paper, config.current_stack(), true)? {
         save_papers(&papers)?;
+        revisions::record(pending)?;
         return Ok(());
-    } else {
-        if let Some(stack) = config.current_stack() {
-            paper.stack.push(stack)
+    }
+    store(papers, paper, bytes, linked, config.current_stack())
+}
+
+enum Source {
+    Arxiv(String),
+    Doi(String),
+}
+
+impl Source {
+    fn parse(line: &str) -> Result<Self> {
+        if arxiv::is_arxiv_link(line) {
+            return Ok(Source::Arxiv(line.to_string()));
         }
+        let doi = normalize_doi(line);
+        if doi.starts_with("10.") && doi.contains('/') {
+            return Ok(Source::Doi(doi));
+        }
+        if Path::new(line).exists() {
+            bail!("local pdfs need a bibtex, add them with bib add -p")
+        }
+        bail!("not an arxiv link or doi")
     }
+}
 
-    // Embed the dude
-    let vector = Point::from_bytes(paper.id.clone(), bytes)?;
-    let mut vectors = load_vectors()?;
-    vectors.insert(paper.id.clone(), vector);
-    save_vectors(&vectors)?;
-    //save it i
-    blog!("Saving", "{}", paper.title);
-    papers.shift_insert(0, paper.id.clone(), paper);
-    save_papers(&papers)?;
+impl Source {
+    /// The doi or arxiv id printed on the first page of a paper, the doi
+    /// first as arxiv versions of published papers often show both.
+    fn identify(first_page: &str) -> Option<Self> {
+        let doi = Regex::new(r"\b10\.\d{4,9}/[^\s\]\[\x22<>]+").unwrap();
+        if let Some(found) = doi.find(first_page) {
+            let doi = found.as_str().trim_end_matches(['.', ',', ';', ')']);
+            return Some(Source::Doi(normalize_doi(doi)));
+        }
+        let arxiv = Regex::new(r"arXiv:(\d{4}\.\d{4,5})").unwrap();
+        arxiv
+            .captures(first_page)
+            .map(|id| Source::Arxiv(format!("https://arxiv.org/abs/{}", &id[1])))
+    }
+}
+
+enum Outcome {
+    Added(String),
+    Duplicate(String),
+    Removed(String),
+    Failed(String),
+}
+
+fn add_source(source: &Source, email: Option<&str>, respect_tombstones: bool) -> Result<Outcome> {
+    let bibtex = match source {
+        Source::Arxiv(url) => arxiv::arxiv2bib(url)?,
+        Source::Doi(doi) => doi2bib(doi)?,
+    };
+    let paper = with_key_style(Paper::from_bibtex(&bibtex)?)?;
+    if tombstoned(&paper, respect_tombstones)? {
+        return Ok(Outcome::Removed(paper.id));
+    }
+    let config = utils::io::read_config_file()?;
+    let mut papers = load_papers()?;
+    if let Some(pending) = merge_duplicate(&mut papers, &paper, config.current_stack(), false)? {
+        save_papers(&papers)?;
+        revisions::record(pending)?;
+        return Ok(Outcome::Duplicate(paper.id));
+    }
+    let bytes = match source {
+        Source::Arxiv(url) => download_arxiv_pdf(url, &paper.id)?,
+        Source::Doi(doi) => {
+            let email = email.ok_or(anyhow!("set email in config to find open access pdfs"))?;
+            let url = unpaywall::pdf_url(doi, email)?.ok_or(anyhow!("no open access pdf"))?;
+            download_pdf(&url, &paper.id)?
+        }
+    };
+    let id = paper.id.clone();
+    store(papers, paper, bytes, None, config.current_stack())?;
+    Ok(Outcome::Added(id))
+}
+
+/// Adds every arxiv link or doi read from stdin, one per line.
+pub fn add_stdin(dry_run: bool, respect_tombstones: bool) -> Result<()> {
+    if !dry_run {
+        utils::io::require_writable()?;
+    }
+    let email = utils::io::read_config_file()?.email;
+    let mut outcomes = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = line?.trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let source = match Source::parse(&line) {
+            Ok(source) => source,
+            Err(err) => {
+                outcomes.push((line, Outcome::Failed(err.to_string())));
+                continue;
+            }
+        };
+        if dry_run {
+            match source {
+                Source::Arxiv(_) => blog!("Would", "add arxiv paper {}", line),
+                Source::Doi(_) => blog!("Would", "add doi {}", line),
+            }
+            continue;
+        }
+        let outcome = add_source(&source, email.as_deref(), respect_tombstones)
+            .unwrap_or_else(|err| Outcome::Failed(err.to_string()));
+        outcomes.push((line, outcome));
+    }
+    print_summary(&outcomes);
+    Ok(())
+}
+
+/// Pdfs under the directory and its subdirectories, in path order.
+fn find_pdfs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pdfs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            pdfs.extend(find_pdfs(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+        {
+            pdfs.push(path);
+        }
+    }
+    pdfs.sort();
+    Ok(pdfs)
+}
+
+/// Identifies a pdf from its first page and stores it, linked where it is
+/// or copied into the library.
+fn import_pdf(path: &Path, link: bool, respect_tombstones: bool) -> Result<Outcome> {
+    let bytes = fs::read(path)?;
+    let first_page = pdf_pages(&bytes)?.into_iter().next().unwrap_or_default();
+    let source = Source::identify(&first_page)
+        .ok_or(anyhow!("no doi or arxiv id found, add it with bib add -p"))?;
+    let bibtex = match &source {
+        Source::Arxiv(url) => arxiv::arxiv2bib(url)?,
+        Source::Doi(doi) => doi2bib(doi)?,
+    };
+    let paper = with_key_style(Paper::from_bibtex(&bibtex)?)?;
+    if tombstoned(&paper, respect_tombstones)? {
+        return Ok(Outcome::Removed(paper.id));
+    }
+    let config = utils::io::read_config_file()?;
+    let mut papers = load_papers()?;
+    if let Some(pending) = merge_duplicate(&mut papers, &paper, config.current_stack(), false)? {
+        save_papers(&papers)?;
+        revisions::record(pending)?;
+        return Ok(Outcome::Duplicate(paper.id));
+    }
+    let linked = match link {
+        true => Some(fs::canonicalize(path)?),
+        false => {
+            utils::io::write_atomic(&utils::io::pdf_path(&paper.id)?, &bytes)?;
+            None
+        }
+    };
+    let id = paper.id.clone();
+    store(papers, paper, bytes, linked, config.current_stack())?;
+    Ok(Outcome::Added(id))
+}
+
+/// Adds every pdf of a folder tree, identified by the doi or arxiv id on
+/// its first page. With `link` the pdfs stay where they are, otherwise they
+/// are copied into the library.
+pub fn import(dir: String, link: bool, dry_run: bool, respect_tombstones: bool) -> Result<()> {
+    if !dry_run {
+        utils::io::require_writable()?;
+    }
+    let mut outcomes = Vec::new();
+    for path in find_pdfs(Path::new(&dir))? {
+        let line = path.display().to_string();
+        if dry_run {
+            let action = if link { "link" } else { "copy" };
+            blog!("Would", "{} {}", action, line);
+            continue;
+        }
+        let outcome = import_pdf(&path, link, respect_tombstones)
+            .unwrap_or_else(|err| Outcome::Failed(err.to_string()));
+        outcomes.push((line, outcome));
+    }
+    print_summary(&outcomes);
     Ok(())
 }
+
+fn print_summary(outcomes: &[(String, Outcome)]) {
+    let width = outcomes.iter().map(|(line, _)| line.len()).max();
+    for (line, outcome) in outcomes {
+        let (tone, status, detail) = match outcome {
+            Outcome::Added(key) => (Tone::Ok, "added", key),
+            Outcome::Duplicate(key) => (Tone::Warn, "duplicate", key),
+            Outcome::Removed(key) => (Tone::Warn, "removed", key),
+            Outcome::Failed(reason) => (Tone::Err, "failed", reason),
+        };
+        println!(
+            "{}{:>12}{} {:<width$} {}",
+            paint(tone),
+            status,
+            reset(),
+            line,
+            detail,
+            width = width.unwrap_or(0)
+        );
+    }
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|(_, o)| f(o)).count();
+    blog!(
+        "Done",
+        "{} added, {} duplicate, {} skipped as removed, {} failed",
+        count(|o| matches!(o, Outcome::Added(_))),
+        count(|o| matches!(o, Outcome::Duplicate(_))),
+        count(|o| matches!(o, Outcome::Removed(_))),
+        count(|o| matches!(o, Outcome::Failed(_)))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identified(text: &str) -> Option<String> {
+        Source::identify(text).map(|source| match source {
+            Source::Arxiv(url) => url,
+            Source::Doi(doi) => doi,
+        })
+    }
+
+    #[test]
+    fn identify_finds_doi() {
+        let page = "Published in Nature. https://doi.org/10.1038/s41586-021-03819-2.";
+        assert_eq!(
+            identified(page).as_deref(),
+            Some("10.1038/s41586-021-03819-2")
+        );
+    }
+
+    #[test]
+    fn identify_prefers_doi_over_arxiv() {
+        let page = "arXiv:2106.01345v2 [cs.LG] (DOI: 10.1145/3447548.3467401)";
+        assert_eq!(identified(page).as_deref(), Some("10.1145/3447548.3467401"));
+    }
+
+    #[test]
+    fn identify_finds_arxiv_id() {
+        let page = "arXiv:1706.03762v7 [cs.CL] 2 Aug 2023";
+        assert_eq!(
+            identified(page).as_deref(),
+            Some("https://arxiv.org/abs/1706.03762")
+        );
+    }
+
+    #[test]
+    fn identify_gives_up_without_ids() {
+        assert!(identified("A Study of Things\nJane Doe").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::annotations::{
+    load_annotations, save_annotations, Bundle, BundlePaper, Layer, Notes, SharedClaim, SharedQuote,
+};
+use crate::base::{find_paper, load_papers, Paper};
+use crate::claims::load_claims;
+use crate::fields::load_fields;
+use crate::parser::bibfile::normalize_title;
+use crate::quotes::load_quotes;
+use crate::utils::clock;
+use crate::utils::fmt::date;
+use crate::utils::io::{read_config_file, require_writable};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+use std::fs;
+
+/// Writes the custom fields, quotes and claims of the given papers, or of the
+/// current stack, to a json bundle others can import.
+pub fn export(keys: Vec<String>, by: Option<String>, output: Option<String>) -> Result<()> {
+    let config = read_config_file()?;
+    let author = match by.or(config.email.clone()) {
+        Some(author) => author,
+        None => bail!("Sign the bundle with --by NAME, or set email in ~/.bib/config.toml"),
+    };
+    let papers = load_papers()?;
+    let selected: Vec<&Paper> = match keys.is_empty() {
+        false => keys
+            .iter()
+            .map(|key| find_paper(&papers, key).ok_or(anyhow!("No paper {}", key)))
+            .collect::<Result<_>>()?,
+        true => {
+            let current = config.current_stack();
+            papers
+                .values()
+                .filter(|paper| current.as_ref().is_none_or(|s| paper.stack.contains(s)))
+                .collect()
+        }
+    };
+
+    let mut fields = load_fields()?;
+    let mut quotes = load_quotes()?;
+    let mut claims = load_claims()?;
+    let mut bundle = Bundle {
+        author,
+        created: clock::now()?,
+        papers: Vec::new(),
+    };
+    for paper in selected {
+        let notes = Notes {
+            fields: fields.remove(&paper.id).unwrap_or_default(),
+            quotes: quotes
+                .remove(&paper.id)
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|topic| {
+                    let name = topic.topic;
+                    topic.quotes.into_iter().map(move |quote| SharedQuote {
+                        topic: name.clone(),
+                        text: quote.text,
+                        page: quote.page,
+                    })
+                })
+                .collect(),
+            claims: claims
+                .remove(&paper.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|claim| SharedClaim {
+                    text: claim.text,
+                    evidence: claim.evidence,
+                    page: claim.page,
+                })
+                .collect(),
+        };
+        if notes.is_empty() {
+            continue;
+        }
+        bundle.papers.push(BundlePaper {
+            key: paper.id.clone(),
+            doi: paper.doi(),
+            title: paper.title.clone(),
+            notes,
+        });
+    }
+    if bundle.papers.is_empty() {
+        bail!("No fields, quotes or claims to share for these papers")
+    }
+
+    let json = serde_json::to_string_pretty(&bundle)?;
+    match output {
+        Some(output) => {
+            fs::write(&output, json)?;
+            blog!(
+                "Saved",
+                "notes on {} papers to {}",
+                bundle.papers.len(),
+                output
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// The library's paper a bundled one is about.
+fn matching<'a>(papers: &'a IndexMap<String, Paper>, shared: &BundlePaper) -> Option<&'a Paper> {
+    if let Some(paper) = shared
+        .doi
+        .as_deref()
+        .and_then(|doi| find_paper(papers, doi))
+    {
+        return Some(paper);
+    }
+    let title = normalize_title(&shared.title);
+    papers
+        .values()
+        .find(|paper| normalize_title(&paper.title) == title)
+}
+
+/// Adds a colleague's bundle as a layer next to the library's own notes,
+/// replacing what an earlier bundle of theirs said about the same papers.
+pub fn import(path: String) -> Result<()> {
+    require_writable()?;
+    let bundle: Bundle = serde_json::from_slice(&fs::read(&path)?)
+        .map_err(|err| anyhow!("{} is not an annotations bundle: {}", path, err))?;
+    let papers = load_papers()?;
+    let mut annotations = load_annotations()?;
+    let imported = clock::now()?;
+    let (mut layered, mut unknown) = (0, 0);
+    for shared in bundle.papers {
+        let paper = match matching(&papers, &shared) {
+            Some(paper) => paper,
+            None => {
+                warn!("Skipped", "{}, not in the library", shared.title);
+                unknown += 1;
+                continue;
+            }
+        };
+        let layers = annotations.entry(paper.id.clone()).or_default();
+        layers.retain(|layer| layer.author != bundle.author);
+        layers.push(Layer {
+            author: bundle.author.clone(),
+            imported,
+            notes: shared.notes,
+        });
+        layered += 1;
+    }
+    save_annotations(&annotations)?;
+    blog!(
+        "Imported",
+        "notes of {} on {} papers, {} not in the library",
+        bundle.author,
+        layered,
+        unknown
+    );
+    Ok(())
+}
+
+/// Prints every imported layer of a paper.
+pub fn show(key: String) -> Result<()> {
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, &key).ok_or(anyhow!("No paper {}", key))?;
+    let annotations = load_annotations()?;
+    let layers = match annotations.get(&paper.id) {
+        Some(layers) if !layers.is_empty() => layers,
+        _ => {
+            blog!("Nothing", "imported about {}", paper.id);
+            return Ok(());
+        }
+    };
+    for layer in layers {
+        println!("# {} (imported {})\n", layer.author, date(layer.imported));
+        for (key, value) in &layer.notes.fields {
+            println!("{} = {}", key, value);
+        }
+        for quote in &layer.notes.quotes {
+            println!("\"{}\" (p. {}, on {})", quote.text, quote.page, quote.topic);
+        }
+        for claim in &layer.notes.claims {
+            println!(
+                "claim: {} (p. {}, {})",
+                claim.text, claim.page, claim.evidence
+            );
+        }
+        println!();
+    }
+    Ok(())
+}
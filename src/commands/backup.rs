@@ -0,0 +1,265 @@
+use crate::base::{find_paper, load_papers};
+use crate::commands::rekey::rename_keys;
+use crate::utils;
+use crate::utils::clock;
+use crate::utils::io::{read_config_file, write_atomic};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// One backed up version of a paper's pdf.
+#[derive(Serialize, Deserialize, Clone)]
+struct Version {
+    sha256: String,
+    size: u64,
+    backed_up: u64,
+}
+
+/// Kept in the backup directory as manifest.json, listing the versions of
+/// each paper's pdf oldest first. The pdfs are stored once under their hash.
+type Manifest = BTreeMap<String, Vec<Version>>;
+
+/// Hash of a local pdf with the size and modification time it had, so
+/// unchanged pdfs are not read again.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Hashed {
+    size: u64,
+    modified: u64,
+    sha256: String,
+}
+
+fn save_hashes(hashes: &BTreeMap<String, Hashed>) -> Result<()> {
+    let encoded: Vec<u8> = serialize(hashes)?;
+    let filename = utils::io::hashes_path()?;
+    utils::io::write_cache(&filename, &encoded)
+}
+
+fn load_hashes() -> Result<BTreeMap<String, Hashed>> {
+    let filename = utils::io::hashes_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    // Hashes can always be computed again
+    Ok(deserialize(&fs::read(filename)?).unwrap_or_default())
+}
+
+fn load_manifest(dir: &Path) -> Result<Manifest> {
+    let filename = dir.join("manifest.json");
+    if !filename.exists() {
+        return Ok(Manifest::new());
+    }
+    let manifest: Manifest = serde_json::from_slice(&fs::read(&filename)?)
+        .map_err(|err| anyhow!("Could not read {}: {}", filename.display(), err))?;
+    // The manifest can be edited by hand, and hashes become file names
+    for (id, versions) in manifest.iter() {
+        for version in versions {
+            let hash = &version.sha256;
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!(
+                    "{} is corrupted, {} has no valid sha256",
+                    filename.display(),
+                    id
+                )
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+fn object_path(dir: &Path, sha256: &str) -> PathBuf {
+    dir.join("objects").join(&sha256[..2]).join(sha256)
+}
+
+fn backup_dir(dir: Option<String>) -> Result<PathBuf> {
+    let dir = match dir.or(read_config_file()?.backup) {
+        Some(dir) => dir,
+        None => bail!("No backup directory, pass --dir or set backup in ~/.bib/config.toml"),
+    };
+    Ok(PathBuf::from(shellexpand::tilde(&dir).to_string()))
+}
+
+fn sha256(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Copies the pdfs not yet in the backup directory. Files are stored under
+/// their hash, so unchanged and duplicate pdfs are never copied twice.
+pub fn backup(dir: Option<String>) -> Result<()> {
+    let dir = backup_dir(dir)?;
+    let mut manifest = load_manifest(&dir)?;
+    let mut hashes = load_hashes()?;
+    let now = clock::now()?;
+    let (mut copied, mut bytes, mut unchanged) = (0, 0, 0);
+    for paper in load_papers()?.values() {
+        let path = paper.pdf_location()?;
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let size = metadata.len();
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |age| age.as_secs());
+        let known = hashes
+            .get(&paper.id)
+            .filter(|hashed| hashed.size == size && hashed.modified == modified)
+            .map(|hashed| hashed.sha256.clone());
+        let hash = match known {
+            Some(hash) if object_path(&dir, &hash).exists() => hash,
+            _ => {
+                let contents = fs::read(&path)?;
+                let hash = sha256(&contents);
+                let object = object_path(&dir, &hash);
+                if !object.exists() {
+                    fs::create_dir_all(object.parent().unwrap())?;
+                    write_atomic(&object, &contents)?;
+                    copied += 1;
+                    bytes += size;
+                }
+                hash
+            }
+        };
+        hashes.insert(
+            paper.id.clone(),
+            Hashed {
+                size,
+                modified,
+                sha256: hash.clone(),
+            },
+        );
+        let versions = manifest.entry(paper.id.clone()).or_default();
+        match versions.last() {
+            Some(last) if last.sha256 == hash => unchanged += 1,
+            _ => versions.push(Version {
+                sha256: hash,
+                size,
+                backed_up: now,
+            }),
+        }
+    }
+    // The manifest goes last so it never points at a missing file
+    write_atomic(
+        &dir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+    )?;
+    save_hashes(&hashes)?;
+    blog!(
+        "Backed up",
+        "{} new pdfs ({:.1} MB) to {}, {} unchanged",
+        copied,
+        bytes as f64 / 1e6,
+        dir.display(),
+        unchanged
+    );
+    Ok(())
+}
+
+/// Restores the latest backed up pdf of a paper, or an earlier version
+/// counting back from it.
+pub fn restore(key: String, dir: Option<String>, back: usize, force: bool) -> Result<()> {
+    let dir = backup_dir(dir)?;
+    let manifest = load_manifest(&dir)?;
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, &key).ok_or(anyhow!("No paper {}", key))?;
+    let versions =
+        manifest
+            .get(&paper.id)
+            .ok_or(anyhow!("{} has no pdf in {}", paper.id, dir.display()))?;
+    let version = versions.iter().rev().nth(back).ok_or(anyhow!(
+        "{} has only {} backed up versions",
+        paper.id,
+        versions.len()
+    ))?;
+    let contents = fs::read(object_path(&dir, &version.sha256))?;
+    if sha256(&contents) != version.sha256 {
+        bail!("The backup of {} is corrupted", paper.id)
+    }
+
+    let target = paper.pdf_location()?;
+    if target.exists() {
+        if sha256(&fs::read(&target)?) == version.sha256 {
+            blog!("Unchanged", "{} matches its backup", target.display());
+            return Ok(());
+        }
+        if !force {
+            warn!("Kept", "{} differs from its backup", target.display());
+            bail!("Pass --force to overwrite it")
+        }
+    }
+    write_atomic(&target, &contents)?;
+    blog!(
+        "Restored",
+        "{} as backed up on {}",
+        paper.id,
+        utils::fmt::date(version.backed_up)
+    );
+    Ok(())
+}
+
+/// Moves renamed keys in the hash cache and in the manifest of the configured
+/// backup directory, so the backed up versions stay with their papers.
+pub fn rename_backed_up(renames: &HashMap<String, String>) -> Result<()> {
+    let mut hashes = load_hashes()?;
+    if rename_keys(&mut hashes, renames) {
+        save_hashes(&hashes)?;
+    }
+    let dir = match read_config_file()?.backup {
+        Some(dir) => PathBuf::from(shellexpand::tilde(&dir).to_string()),
+        None => return Ok(()),
+    };
+    if !dir.exists() {
+        warn!(
+            "Skipped",
+            "{} is not reachable, its manifest keeps the old keys",
+            dir.display()
+        );
+        return Ok(());
+    }
+    let mut manifest = load_manifest(&dir)?;
+    if rename_keys(&mut manifest, renames) {
+        write_atomic(
+            &dir.join("manifest.json"),
+            serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_with(name: &str, hash: &str) -> Result<Manifest> {
+        let dir = std::env::temp_dir().join(format!("bib-manifest-{}", name));
+        fs::create_dir_all(&dir).unwrap();
+        let json = format!(
+            r#"{{"doe2020": [{{"sha256": "{}", "size": 1, "backed_up": 0}}]}}"#,
+            hash
+        );
+        fs::write(dir.join("manifest.json"), json).unwrap();
+        let manifest = load_manifest(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        manifest
+    }
+
+    #[test]
+    fn accepts_sha256_hashes() {
+        let manifest = manifest_with("valid", &sha256(b"pdf")).unwrap();
+        assert_eq!(manifest["doe2020"][0].sha256, sha256(b"pdf"));
+    }
+
+    #[test]
+    fn refuses_hashes_that_are_not_sha256() {
+        for (case, hash) in ["", "a", "é", &"g".repeat(64)].iter().enumerate() {
+            let name = format!("invalid-{}", case);
+            assert!(manifest_with(&name, hash).is_err(), "{}", hash);
+        }
+    }
+}
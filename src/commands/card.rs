@@ -0,0 +1,167 @@
+use crate::base::{find_paper, load_papers, Paper};
+use crate::commands::cv::venue;
+use crate::commands::find::sentences;
+use crate::embedding::load_pages;
+use crate::fields::load_fields;
+use crate::quotes::{load_quotes, Quote};
+use crate::warn;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// Everything a slide about a paper shows.
+struct Card {
+    citation: String,
+    tldr: Option<String>,
+    figure: Option<String>,
+    takeaways: Vec<Quote>,
+}
+
+/// Prints a one-slide summary of a paper as markdown or a beamer frame, made
+/// from what is already stored about it: the abstract, custom `tldr` and
+/// `figure` fields, the captions of embedded pages and the best quotes.
+pub fn card(key: String, format: String, takeaways: usize) -> Result<()> {
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, &key).ok_or(anyhow!("No paper {}", key))?;
+    let fields = load_fields()?;
+    let fields = fields.get(&paper.id);
+    let field = |name: &str| fields.and_then(|fields| fields.get(name)).cloned();
+
+    let tldr = field("tldr").or_else(|| {
+        paper
+            .bibtex_field("abstract")
+            .and_then(|text| sentences(&text).into_iter().next())
+    });
+    let figure = match field("figure") {
+        Some(caption) => Some(caption),
+        None => load_pages()?
+            .get(&paper.id)
+            .and_then(|pages| figure_caption(&pages.pages)),
+    };
+    let mut quotes: Vec<Quote> = load_quotes()?
+        .remove(&paper.id)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|topic| topic.quotes)
+        .collect();
+    quotes.sort_by(|a, b| b.score.total_cmp(&a.score));
+    quotes.dedup_by(|a, b| a.text == b.text);
+    quotes.truncate(takeaways);
+
+    if tldr.is_none() {
+        warn!("Missing", "no abstract or tldr field for {}", paper.id);
+    }
+    if quotes.is_empty() && takeaways > 0 {
+        warn!(
+            "Missing",
+            "no takeaways, store some with bib quotes {} TOPIC", paper.id
+        );
+    }
+    let card = Card {
+        citation: citation(paper),
+        tldr,
+        figure,
+        takeaways: quotes,
+    };
+    match format.as_str() {
+        "beamer" => println!("{}", beamer(paper, &card)),
+        _ => println!("{}", markdown(paper, &card)),
+    }
+    Ok(())
+}
+
+fn citation(paper: &Paper) -> String {
+    let authors = paper.full_authors();
+    let authors = match authors.len() {
+        0..=2 => authors.join(" and "),
+        _ => format!("{} et al.", authors[0]),
+    };
+    match venue(paper) {
+        Some(venue) => format!("{} ({}). {}", authors, paper.year, venue),
+        None => format!("{} ({})", authors, paper.year),
+    }
+}
+
+/// Caption of the lowest numbered figure found on the pages.
+fn figure_caption(pages: &[String]) -> Option<String> {
+    let caption = Regex::new(r"\b(?:Figure|Fig\.)\s*(\d+)\s*[:.]\s+(.+?\.)(?:\s|$)").unwrap();
+    pages
+        .iter()
+        .map(|page| page.split_whitespace().collect::<Vec<_>>().join(" "))
+        .flat_map(|page| {
+            caption
+                .captures_iter(&page)
+                .filter_map(|c| Some((c[1].parse::<usize>().ok()?, c[2].to_string())))
+                .collect::<Vec<_>>()
+        })
+        .filter(|(_, text)| text.split_whitespace().count() > 3)
+        .min_by_key(|(number, _)| *number)
+        .map(|(number, text)| format!("Figure {}: {}", number, text))
+}
+
+fn markdown(paper: &Paper, card: &Card) -> String {
+    let mut slide = format!(
+        "## {}\n\n*{}* [@{}]\n",
+        paper.title, card.citation, paper.id
+    );
+    if let Some(tldr) = &card.tldr {
+        slide.push_str(&format!("\n**TL;DR** {}\n", tldr));
+    }
+    if let Some(figure) = &card.figure {
+        slide.push_str(&format!("\n**Key figure** {}\n", figure));
+    }
+    if !card.takeaways.is_empty() {
+        slide.push_str("\n**Takeaways**\n\n");
+        for quote in &card.takeaways {
+            slide.push_str(&format!("- \"{}\" (p. {})\n", quote.text, quote.page));
+        }
+    }
+    slide
+}
+
+fn beamer(paper: &Paper, card: &Card) -> String {
+    let mut slide = format!(
+        "\\begin{{frame}}{{{}}}\n  \\framesubtitle{{{}}}\n",
+        latex(&paper.title),
+        latex(&card.citation)
+    );
+    if let Some(tldr) = &card.tldr {
+        slide.push_str(&format!("  \\textbf{{TL;DR}} {}\n\n", latex(tldr)));
+    }
+    if let Some(figure) = &card.figure {
+        slide.push_str(&format!("  \\textbf{{Key figure}} {}\n\n", latex(figure)));
+    }
+    if !card.takeaways.is_empty() {
+        slide.push_str("  \\begin{itemize}\n");
+        for quote in &card.takeaways {
+            slide.push_str(&format!(
+                "    \\item ``{}'' (p.~{})\n",
+                latex(&quote.text),
+                quote.page
+            ));
+        }
+        slide.push_str("  \\end{itemize}\n");
+    }
+    slide.push_str(&format!(
+        "  \\vfill {{\\footnotesize \\cite{{{}}}}}\n\\end{{frame}}",
+        paper.id
+    ));
+    slide
+}
+
+/// Escapes the characters LaTeX treats specially.
+fn latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
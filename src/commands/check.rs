@@ -0,0 +1,62 @@
+use crate::base::load_papers;
+use crate::parser::bibfile::{normalize_doi, normalize_title};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+use biblatex::{Bibliography, ChunksExt};
+use std::collections::HashMap;
+use std::fs;
+
+/// Reports which entries of a bib file are in the library and under which key.
+/// Fails when keys differ from the library ones, so bib exits non zero.
+pub fn check(path: String) -> Result<()> {
+    let content = fs::read_to_string(&path)?;
+    let bibliography =
+        Bibliography::parse(&content).map_err(|e| anyhow!("Failed to parse {}: {:?}", path, e))?;
+    let papers = load_papers()?;
+
+    let mut by_doi = HashMap::new();
+    let mut by_title = HashMap::new();
+    for paper in papers.values() {
+        if let Some(doi) = paper.doi() {
+            by_doi.insert(doi, paper.id.clone());
+        }
+        by_title.insert(normalize_title(&paper.title), paper.id.clone());
+    }
+
+    let (mut matched, mut renamed, mut missing) = (0, 0, 0);
+    for entry in bibliography.iter() {
+        let doi = entry
+            .get("doi")
+            .map(|doi| normalize_doi(&doi.format_verbatim()));
+        let title = entry
+            .get("title")
+            .map(|title| normalize_title(&title.format_verbatim()));
+        let found = doi
+            .and_then(|doi| by_doi.get(&doi))
+            .or_else(|| title.and_then(|title| by_title.get(&title)))
+            .or_else(|| papers.get_key_value(&entry.key).map(|(key, _)| key));
+        match found {
+            Some(key) if *key == entry.key => matched += 1,
+            Some(key) => {
+                renamed += 1;
+                warn!("Renamed", "{} is {} in the library", entry.key, key);
+            }
+            None => {
+                missing += 1;
+                warn!("Missing", "{} is not in the library", entry.key);
+            }
+        }
+    }
+    blog!(
+        "Checked",
+        "{} entries: {} matching, {} renamed, {} missing",
+        bibliography.len(),
+        matched,
+        renamed,
+        missing
+    );
+    if renamed > 0 {
+        bail!("{} keys differ from the library ones", renamed)
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::base::{find_paper, load_papers, Paper};
+use crate::claims::{classify, load_claims, save_claims, Claim, Claims};
+use crate::commands::find::{paper_pages, sentences, MAX_SENTENCE_WORDS};
+use crate::embedding::{encode_many_with, encode_query, encode_with, text_model};
+use crate::ranking::top_k;
+use crate::utils::io::{read_config_file, require_writable};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+use fastembed::TextEmbedding;
+
+/// Extracts the claims of a paper, or of every paper not extracted yet, then
+/// searches all stored claims for the query.
+pub fn claims(query: Option<String>, extract: bool, key: Option<String>, max: usize) -> Result<()> {
+    if extract {
+        extract_claims(key.as_deref())?;
+    } else if key.is_some() {
+        bail!("--in only applies to --extract");
+    }
+    match query {
+        Some(query) => search(&query, max),
+        None if extract => Ok(()),
+        None => bail!("Give a claim to look for, or --extract to find claims in papers"),
+    }
+}
+
+fn extract_claims(key: Option<&str>) -> Result<()> {
+    require_writable()?;
+    read_config_file()?.features.require_deep_find()?;
+    let papers = load_papers()?;
+    let mut stored = load_claims()?;
+    let targets: Vec<&Paper> = match key {
+        Some(key) => vec![find_paper(&papers, key).ok_or(anyhow!("No paper {}", key))?],
+        None => papers
+            .values()
+            .filter(|paper| !stored.contains_key(&paper.id))
+            .collect(),
+    };
+    if targets.is_empty() {
+        blog!("Nothing", "to extract, every paper has its claims stored");
+        return Ok(());
+    }
+    let model = text_model()?;
+    let dim = encode_with(&model, "dimension probe")?.len();
+    for paper in targets {
+        let found = match paper_claims(&model, paper, dim) {
+            Ok(found) => found,
+            Err(err) => {
+                warn!("Skipped", "{}: {}", paper.id, err);
+                continue;
+            }
+        };
+        blog!("Extracted", "{} claims of {}", found.len(), paper.id);
+        stored.insert(paper.id.clone(), found);
+        // Saved as it goes, a long pass can be stopped and picked up later
+        save_claims(&stored)?;
+    }
+    Ok(())
+}
+
+/// Sentences of the paper that announce a finding, with their page.
+fn paper_claims(model: &TextEmbedding, paper: &Paper, dim: usize) -> Result<Vec<Claim>> {
+    let pages = paper_pages(paper, dim)?;
+    let mut found = Vec::new();
+    for (page, text) in pages.pages.iter().enumerate() {
+        for sentence in sentences(text) {
+            if sentence.split_whitespace().count() > MAX_SENTENCE_WORDS {
+                continue;
+            }
+            if let Some(evidence) = classify(&sentence) {
+                found.push((sentence, evidence, page + 1));
+            }
+        }
+    }
+    let texts: Vec<String> = found.iter().map(|(text, _, _)| text.clone()).collect();
+    let coords = match texts.is_empty() {
+        true => Vec::new(),
+        false => encode_many_with(model, &texts)?,
+    };
+    Ok(found
+        .into_iter()
+        .zip(coords)
+        .map(|((text, evidence, page), coords)| Claim {
+            text,
+            evidence,
+            page,
+            coords,
+        })
+        .collect())
+}
+
+/// Prints the stored claims closest to the query.
+fn search(query: &str, max: usize) -> Result<()> {
+    read_config_file()?
+        .features
+        .require_embeddings("Searching claims")?;
+    let stored: Claims = load_claims()?;
+    if stored.is_empty() {
+        bail!("No claims stored, extract them with bib claims --extract");
+    }
+    let coords = encode_query(query)?;
+    let scored = stored
+        .iter()
+        .flat_map(|(id, claims)| claims.iter().map(move |claim| (id, claim)))
+        .filter(|(_, claim)| claim.coords.len() == coords.len())
+        .map(|(id, claim)| ((id, claim), dotzilla::dot(&coords, &claim.coords)));
+    for ((id, claim), _) in top_k(scored, max) {
+        println!(
+            "\"{}\" ({}, p. {}, {})\n",
+            claim.text, id, claim.page, claim.evidence
+        );
+    }
+    Ok(())
+}
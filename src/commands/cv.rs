@@ -0,0 +1,107 @@
+use crate::base::{load_papers, Paper};
+use crate::fields::{self, load_fields};
+use crate::utils::collate;
+use crate::utils::io::read_config_file;
+use anyhow::Result;
+
+/// Publication types in the order they appear on a cv.
+const SECTIONS: [(&str, &[&str]); 7] = [
+    ("Journal articles", &["article"]),
+    ("Conference papers", &["inproceedings", "proceedings"]),
+    ("Books", &["book", "booklet"]),
+    ("Book chapters", &["inbook", "incollection"]),
+    ("Theses", &["phdthesis", "mastersthesis"]),
+    ("Technical reports", &["techreport"]),
+    ("Preprints and other", &[]),
+];
+
+fn section(paper: &Paper) -> &'static str {
+    SECTIONS
+        .iter()
+        .find(|(_, types)| types.contains(&paper.kind.as_str()))
+        .map_or(SECTIONS[SECTIONS.len() - 1].0, |(name, _)| name)
+}
+
+fn with_prefix(prefix: &str, place: Option<String>) -> String {
+    match place {
+        Some(place) => format!("{}, {}", prefix, place),
+        None => prefix.to_string(),
+    }
+}
+
+/// Where the paper appeared, worded for its publication type.
+pub fn venue(paper: &Paper) -> Option<String> {
+    let field = |name: &str| paper.bibtex_field(name);
+    match paper.kind.as_str() {
+        "phdthesis" => Some(with_prefix("PhD thesis", field("school"))),
+        "mastersthesis" => Some(with_prefix("Master's thesis", field("school"))),
+        "techreport" => Some(with_prefix("Technical report", field("institution"))),
+        "book" | "booklet" => field("publisher"),
+        "inbook" | "incollection" => field("booktitle").map(|book| format!("In {}", book)),
+        "inproceedings" => field("booktitle"),
+        "misc" if field("eprint").is_some() => field("eprint").map(|id| format!("arXiv:{}", id)),
+        _ => field("journal"),
+    }
+}
+
+fn markdown_item(paper: &Paper) -> String {
+    let mut item = format!(
+        "- {} ({}). *{}*.",
+        paper.full_authors().join(", "),
+        paper.year,
+        paper.title
+    );
+    if let Some(venue) = venue(paper) {
+        item.push_str(&format!(" {}.", venue));
+    }
+    if let Some(doi) = paper.doi() {
+        item.push_str(&format!(" [doi:{}](https://doi.org/{})", doi, doi));
+    }
+    item
+}
+
+/// Prints the papers marked as mine grouped by type and year, newest first,
+/// and alphabetically by title within a year.
+pub fn cv(since: Option<i64>, format: String) -> Result<()> {
+    let config = read_config_file()?;
+    let papers = load_papers()?;
+    let mut mine: Vec<&Paper> = config
+        .mine
+        .iter()
+        .filter_map(|key| papers.get(key))
+        .filter(|paper| since.is_none_or(|since| paper.year >= since))
+        .collect();
+    mine.sort_by(|a, b| {
+        b.year
+            .cmp(&a.year)
+            .then_with(|| collate::compare(&a.title, &b.title))
+    });
+
+    if format == "bib" {
+        let custom = load_fields()?;
+        for paper in mine {
+            println!(
+                "{}\n",
+                fields::with_fields(&paper.bibtex, custom.get(&paper.id))
+            );
+        }
+        return Ok(());
+    }
+
+    for (name, _) in SECTIONS.iter() {
+        let papers: Vec<&&Paper> = mine.iter().filter(|p| section(p) == *name).collect();
+        if papers.is_empty() {
+            continue;
+        }
+        println!("## {}\n", name);
+        let mut year = None;
+        for paper in papers {
+            if year != Some(paper.year) {
+                year = Some(paper.year);
+                println!("### {}\n", paper.year);
+            }
+            println!("{}\n", markdown_item(paper));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::base::load_papers;
+use crate::utils::clock;
+use crate::utils::fmt::{paint, parse_date, reset, Tone};
+use crate::utils::io::{read_config_file, require_writable, save_config_file, Deadline};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+
+/// Deadlines this close are highlighted.
+const SOON_DAYS: i64 = 14;
+
+/// Lists the upcoming deadlines, soonest first, with the number of papers in
+/// the stack named after each venue. `all` includes past deadlines too.
+pub fn list(all: bool) -> Result<()> {
+    let config = read_config_file()?;
+    let papers = load_papers()?;
+    let today = (clock::now()? / 86400) as i64;
+    let mut deadlines: Vec<(i64, &Deadline)> = config
+        .deadlines
+        .iter()
+        .filter_map(|deadline| {
+            let day = parse_date(&deadline.date)? / 86400;
+            Some((day as i64 - today, deadline))
+        })
+        .filter(|(left, _)| all || *left >= 0)
+        .collect();
+    deadlines.sort_by_key(|(left, _)| *left);
+    if deadlines.is_empty() {
+        blog!(
+            "Nothing",
+            "due, add a deadline with bib deadlines add VENUE DATE"
+        );
+        return Ok(());
+    }
+    let width = deadlines
+        .iter()
+        .map(|(_, deadline)| deadline.venue.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (left, deadline) in deadlines {
+        let tone = match left {
+            left if left < 0 => Tone::Muted,
+            left if left <= SOON_DAYS => Tone::Warn,
+            _ => Tone::Ok,
+        };
+        let when = match left {
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            left if left < 0 => format!("{} days ago", -left),
+            left => format!("in {} days", left),
+        };
+        let tagged = papers
+            .values()
+            .filter(|paper| {
+                paper
+                    .stack
+                    .iter()
+                    .any(|stack| stack.name.eq_ignore_ascii_case(&deadline.venue))
+            })
+            .count();
+        println!(
+            "{:<width$}  {}  {}{:>12}{}  {} papers  {}",
+            deadline.venue,
+            deadline.date,
+            paint(tone),
+            when,
+            reset(),
+            tagged,
+            deadline.note.as_deref().unwrap_or(""),
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Tracks a venue's deadline, replacing an earlier one for the same venue.
+pub fn add(venue: String, date: String, note: Option<String>) -> Result<()> {
+    require_writable()?;
+    if parse_date(&date).is_none() {
+        bail!("Invalid date {}, expected YYYY-MM-DD", date)
+    }
+    let mut config = read_config_file()?;
+    config
+        .deadlines
+        .retain(|deadline| !deadline.venue.eq_ignore_ascii_case(&venue));
+    if !config
+        .stacks
+        .iter()
+        .any(|s| s.name.eq_ignore_ascii_case(&venue))
+    {
+        warn!(
+            "Suggestion",
+            "create the stack with bib stack {} new to track its papers", venue
+        );
+    }
+    blog!("Tracking", "{} due {}", venue, date);
+    config.deadlines.push(Deadline { venue, date, note });
+    save_config_file(&config)
+}
+
+pub fn drop(venue: String) -> Result<()> {
+    require_writable()?;
+    let mut config = read_config_file()?;
+    let before = config.deadlines.len();
+    config
+        .deadlines
+        .retain(|deadline| !deadline.venue.eq_ignore_ascii_case(&venue));
+    if config.deadlines.len() == before {
+        return Err(anyhow!("No deadline for {}", venue));
+    }
+    save_config_file(&config)?;
+    blog!("Dropped", "deadline of {}", venue);
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use crate::base::{save_papers, Paper};
+use crate::embedding::{save_vectors, save_vectors_model, Point};
+use crate::parser::arxiv::download_arxiv_pdf;
+use crate::stacks::Stack;
+use crate::utils::io::{save_config_file, Config};
+use crate::{blog, warn};
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Left in the demo directory so it is only ever replaced by another demo.
+const MARKER: &str = ".bib-demo";
+
+/// Open access papers of the demo library, with their stack.
+const SAMPLES: [(&str, &str); 8] = [
+    (
+        "vaswani2017attention",
+        "@misc{vaswani2017attention,
+    title = {Attention Is All You Need},
+    author = {Vaswani, Ashish and Shazeer, Noam and Parmar, Niki and Uszkoreit, Jakob and Jones, Llion and Gomez, Aidan N. and Kaiser, Lukasz and Polosukhin, Illia},
+    year = {2017},
+    eprint = {1706.03762},
+    archiveprefix = {arXiv},
+    abstract = {The Transformer is a sequence transduction model based solely on attention, dispensing with recurrence and convolutions. It reaches state of the art translation quality while being far more parallelizable and faster to train.}
+}",
+    ),
+    (
+        "devlin2019bert",
+        "@misc{devlin2019bert,
+    title = {BERT: Pre-training of Deep Bidirectional Transformers for Language Understanding},
+    author = {Devlin, Jacob and Chang, Ming-Wei and Lee, Kenton and Toutanova, Kristina},
+    year = {2019},
+    eprint = {1810.04805},
+    archiveprefix = {arXiv},
+    abstract = {BERT pre-trains deep bidirectional language representations from unlabeled text by conditioning on both left and right context. Fine-tuned with one extra output layer it sets new results on eleven language understanding tasks.}
+}",
+    ),
+    (
+        "mikolov2013efficient",
+        "@misc{mikolov2013efficient,
+    title = {Efficient Estimation of Word Representations in Vector Space},
+    author = {Mikolov, Tomas and Chen, Kai and Corrado, Greg and Dean, Jeffrey},
+    year = {2013},
+    eprint = {1301.3781},
+    archiveprefix = {arXiv},
+    abstract = {Two model architectures learn continuous vector representations of words from very large corpora at low cost. The word vectors capture syntactic and semantic word similarities.}
+}",
+    ),
+    (
+        "he2016deep",
+        "@misc{he2016deep,
+    title = {Deep Residual Learning for Image Recognition},
+    author = {He, Kaiming and Zhang, Xiangyu and Ren, Shaoqing and Sun, Jian},
+    year = {2016},
+    eprint = {1512.03385},
+    archiveprefix = {arXiv},
+    abstract = {Residual networks learn functions relative to their layer inputs, which makes much deeper convolutional networks easy to optimize. They won the ImageNet classification and COCO detection challenges.}
+}",
+    ),
+    (
+        "dosovitskiy2021image",
+        "@misc{dosovitskiy2021image,
+    title = {An Image is Worth 16x16 Words: Transformers for Image Recognition at Scale},
+    author = {Dosovitskiy, Alexey and Beyer, Lucas and Kolesnikov, Alexander and Weissenborn, Dirk and Zhai, Xiaohua and Unterthiner, Thomas and Dehghani, Mostafa and Minderer, Matthias and Heigold, Georg and Gelly, Sylvain and Uszkoreit, Jakob and Houlsby, Neil},
+    year = {2021},
+    eprint = {2010.11929},
+    archiveprefix = {arXiv},
+    abstract = {A pure transformer applied to sequences of image patches performs very well on image classification. Pre-trained on large datasets the vision transformer matches convolutional networks with less compute.}
+}",
+    ),
+    (
+        "goodfellow2014generative",
+        "@misc{goodfellow2014generative,
+    title = {Generative Adversarial Networks},
+    author = {Goodfellow, Ian J. and Pouget-Abadie, Jean and Mirza, Mehdi and Xu, Bing and Warde-Farley, David and Ozair, Sherjil and Courville, Aaron and Bengio, Yoshua},
+    year = {2014},
+    eprint = {1406.2661},
+    archiveprefix = {arXiv},
+    abstract = {A generative model is trained against a discriminative model that learns to tell its samples from the data. The two are trained together as a minimax game.}
+}",
+    ),
+    (
+        "ho2020denoising",
+        "@misc{ho2020denoising,
+    title = {Denoising Diffusion Probabilistic Models},
+    author = {Ho, Jonathan and Jain, Ajay and Abbeel, Pieter},
+    year = {2020},
+    eprint = {2006.11239},
+    archiveprefix = {arXiv},
+    abstract = {Diffusion probabilistic models generate high quality images by learning to reverse a gradual noising process. The training objective connects them to denoising score matching.}
+}",
+    ),
+    (
+        "kingma2015adam",
+        "@misc{kingma2015adam,
+    title = {Adam: A Method for Stochastic Optimization},
+    author = {Kingma, Diederik P. and Ba, Jimmy},
+    year = {2015},
+    eprint = {1412.6980},
+    archiveprefix = {arXiv},
+    abstract = {Adam is a first-order gradient-based optimization method for stochastic objectives based on adaptive estimates of lower-order moments. It is simple, memory efficient and suited to problems with large data or many parameters.}
+}",
+    ),
+];
+
+/// Stack of each sample paper, papers not listed are in none.
+fn sample_stack(id: &str) -> Option<&'static str> {
+    match id {
+        "vaswani2017attention" | "devlin2019bert" | "mikolov2013efficient" => Some("language"),
+        "he2016deep" | "dosovitskiy2021image" | "goodfellow2014generative" | "ho2020denoising" => {
+            Some("vision")
+        }
+        _ => None,
+    }
+}
+
+/// Creates a throwaway library of sample papers to try bib on. Its
+/// embeddings are synthetic, so nothing is downloaded unless `pdfs` is set.
+pub fn demo(dir: Option<String>, pdfs: bool) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => PathBuf::from(shellexpand::tilde(&dir).to_string()),
+        None => env::temp_dir().join("bib-demo"),
+    };
+    // bib runs again on the demo directory to fill it, so every store is
+    // written there without changing where this process reads and writes
+    if env::var_os("BIB_HOME").map(PathBuf::from).as_ref() != Some(&dir) {
+        let mut command = Command::new(env::current_exe()?);
+        command
+            .env("BIB_HOME", &dir)
+            .arg("demo")
+            .arg("--dir")
+            .arg(&dir);
+        if pdfs {
+            command.arg("--pdfs");
+        }
+        if !command.status()?.success() {
+            bail!("Could not create the demo library in {}", dir.display())
+        }
+        return Ok(());
+    }
+    if dir.exists() {
+        if !dir.join(MARKER).exists() && fs::read_dir(&dir)?.next().is_some() {
+            bail!("{} is not empty and not a demo library", dir.display())
+        }
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(MARKER), "")?;
+
+    let mut config = Config::default();
+    for name in ["language", "vision"] {
+        let stack = Stack::new(name, &config.stacks)?;
+        config.stacks.push(stack);
+    }
+    config.features.synthetic = true;
+    config.features.deep_find = false;
+    save_config_file(&config)?;
+
+    let mut papers = IndexMap::new();
+    let mut vectors = BTreeMap::new();
+    for (id, bibtex) in SAMPLES {
+        let mut paper = Paper::from_bibtex(bibtex)?;
+        if let Some(name) = sample_stack(id) {
+            paper
+                .stack
+                .extend(config.stacks.iter().find(|s| s.name == name).cloned());
+        }
+        let text = format!(
+            "{}. {}",
+            paper.title,
+            paper.bibtex_field("abstract").unwrap_or_default()
+        );
+        vectors.insert(paper.id.clone(), Point::synthetic(paper.id.clone(), &text));
+        if pdfs {
+            let link = format!(
+                "https://arxiv.org/abs/{}",
+                paper.bibtex_field("eprint").unwrap()
+            );
+            blog!("Downloading", "{}", link);
+            if let Err(err) = download_arxiv_pdf(&link, &paper.id) {
+                warn!("Skipped", "pdf of {}: {}", paper.id, err);
+            }
+        }
+        papers.insert(paper.id.clone(), paper);
+    }
+    save_vectors(&vectors)?;
+    save_vectors_model("synthetic")?;
+    save_papers(&papers)?;
+
+    blog!(
+        "Created",
+        "demo library of {} papers in {}",
+        papers.len(),
+        dir.display()
+    );
+    println!("\nTry it with, for example:\n");
+    println!("  BIB_HOME={} bib", dir.display());
+    println!(
+        "  BIB_HOME={} bib find \"attention for images\"",
+        dir.display()
+    );
+    println!("  BIB_HOME={} bib stack vision\n", dir.display());
+    println!("Your own library in ~/.bib is left untouched.");
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crate::base::{load_papers, save_papers, Paper};
+use crate::parser::bibfile::{extract_entry, repair_bibtex};
+use crate::revisions::{self, Revision, Revisions};
+use crate::utils::diff::accept_rewrite;
+use crate::utils::io::require_writable;
+use crate::{blog, warn};
+use anyhow::Result;
+
+/// Finds stored entries the bibtex parser rejects and, with `fix_bibtex`,
+/// repairs the ones whose key survives the repair. Every repair is shown
+/// for approval unless `yes`, and the old entry is kept in revisions.bin.
+pub fn doctor(fix_bibtex: bool, yes: bool) -> Result<()> {
+    if fix_bibtex {
+        require_writable()?;
+    }
+    let mut papers = load_papers()?;
+    let broken: Vec<String> = papers
+        .values()
+        .filter(|paper| extract_entry(&paper.bibtex).is_err())
+        .map(|paper| paper.id.clone())
+        .collect();
+    if broken.is_empty() {
+        blog!("Healthy", "all {} entries parse", papers.len());
+        return Ok(());
+    }
+    if !fix_bibtex {
+        for key in broken.iter() {
+            warn!("Malformed", "bibtex of {}", key);
+        }
+        warn!("Suggestion", "run bib doctor --fix-bibtex to repair them");
+        return Ok(());
+    }
+
+    let mut pending = Revisions::new();
+    for key in broken.iter() {
+        let paper = papers.get_mut(key).unwrap();
+        let repaired = repair_bibtex(&paper.bibtex)
+            .map(|bibtex| Paper::from_stored_bibtex(&bibtex))
+            .and_then(|repaired| repaired.ok());
+        match repaired {
+            Some(repaired) if repaired.id == paper.id => {
+                blog!("Repair", "{}", key);
+                if !accept_rewrite(&paper.bibtex, &repaired.bibtex, yes)? {
+                    continue;
+                }
+                let revision = Revision::new("doctor", &paper.bibtex, &repaired.bibtex)?;
+                pending.insert(key.clone(), vec![revision]);
+                *paper = Paper {
+                    stack: std::mem::take(&mut paper.stack),
+                    ..repaired
+                };
+                blog!("Repaired", "{}", key);
+            }
+            Some(repaired) => warn!(
+                "Unfixable",
+                "{} repairs to a different key {}, add it again", key, repaired.id
+            ),
+            None => warn!("Unfixable", "{}, add it again", key),
+        }
+    }
+    let fixed = pending.len();
+    if fixed > 0 {
+        save_papers(&papers)?;
+        revisions::record(pending)?;
+    }
+    blog!(
+        "Done",
+        "{} of {} malformed entries repaired",
+        fixed,
+        broken.len()
+    );
+    Ok(())
+}
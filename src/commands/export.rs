@@ -1,7 +1,9 @@
 use crate::base::{load_papers, Paper};
+use crate::fields::{self, load_fields, Fields};
 use crate::utils::io::read_config_file;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use indexmap::IndexMap;
+use std::collections::BTreeMap;
 use std::io::{self, Write};
 
 fn filter_by_stack(papers: &IndexMap<String, Paper>) -> Result<Vec<String>> {
@@ -18,18 +20,135 @@ fn filter_by_stack(papers: &IndexMap<String, Paper>) -> Result<Vec<String>> {
     Ok(indicies)
 }
 
-pub fn export() -> Result<()> {
+/// Columns of a csv export when none are given.
+pub const DEFAULT_COLUMNS: &str = "key,title,authors,year,doi,tags";
+
+/// Quotes a csv value when it contains separators, quotes or line breaks.
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Value of a column for a paper. Columns that are not built in are looked up
+/// in the custom fields first and then in the bibtex entry.
+fn column(paper: &Paper, custom: Option<&Fields>, name: &str) -> String {
+    match name {
+        "key" => paper.id.clone(),
+        "title" => paper.title.clone(),
+        "authors" => paper.full_authors().join("; "),
+        "year" => paper.year.to_string(),
+        "doi" => paper.doi().unwrap_or_default(),
+        "tags" | "stacks" => paper
+            .stack
+            .iter()
+            .map(|stack| stack.name.clone())
+            .collect::<Vec<_>>()
+            .join("; "),
+        "type" => paper.kind.clone(),
+        "link" => paper.link().unwrap_or_default(),
+        _ => custom
+            .and_then(|fields| fields.get(name).cloned())
+            .or_else(|| paper.bibtex_field(name))
+            .unwrap_or_default(),
+    }
+}
+
+fn to_csv(papers: &[&Paper], custom: &BTreeMap<String, Fields>, columns: &[String]) -> String {
+    let mut rows = vec![columns
+        .iter()
+        .map(|name| csv_escape(name))
+        .collect::<Vec<_>>()
+        .join(",")];
+    for paper in papers {
+        let row = columns
+            .iter()
+            .map(|name| csv_escape(&column(paper, custom.get(&paper.id), name)))
+            .collect::<Vec<_>>()
+            .join(",");
+        rows.push(row);
+    }
+    rows.join("\n") + "\n"
+}
+
+pub fn export(filters: Vec<String>, format: String, columns: Vec<String>) -> Result<()> {
     let papers = load_papers()?;
+    let custom = load_fields()?;
+    let filters = filters
+        .iter()
+        .map(|f| fields::parse_assignment(f))
+        .collect::<Result<Vec<_>>>()?;
     let indices = filter_by_stack(&papers)?;
 
-    let bibtex_entries: String = indices
+    let selected: Vec<&Paper> = indices
         .iter()
+        .filter(|id| fields::matches(custom.get(*id), &filters))
         .filter_map(|id| papers.get(id))
-        .map(|paper| paper.bibtex.clone())
-        .collect::<Vec<String>>()
-        .join("\n");
-    // Print the concatenated BibTeX entries to stdout
-    io::stdout().write_all(bibtex_entries.as_bytes())?;
+        .collect();
+
+    let output = match format.as_str() {
+        "csv" => {
+            let columns: Vec<String> = columns
+                .iter()
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect();
+            if columns.is_empty() {
+                bail!("No columns to export");
+            }
+            to_csv(&selected, &custom, &columns)
+        }
+        _ => selected
+            .iter()
+            .map(|paper| fields::with_fields(&paper.bibtex, custom.get(&paper.id)))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+    // Print the concatenated entries to stdout
+    io::stdout().write_all(output.as_bytes())?;
     io::stdout().flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_plain_values_alone() {
+        assert_eq!(csv_escape("doe2020"), "doe2020");
+        assert_eq!(csv_escape(""), "");
+    }
+
+    #[test]
+    fn quotes_commas_quotes_and_line_breaks() {
+        assert_eq!(csv_escape("Doe, Jane"), "\"Doe, Jane\"");
+        assert_eq!(csv_escape("a \"quoted\" word"), "\"a \"\"quoted\"\" word\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_escape("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn writes_escaped_rows() {
+        let paper = Paper::from_bibtex(
+            "@article{doe2020,\n  title = {Things, and Stuff},\n  author = {Doe, Jane},\n  year = {2020},\n  journal = {Journal of \"Things\"},\n}",
+        )
+        .unwrap();
+        let mut custom = BTreeMap::new();
+        custom.insert(
+            paper.id.clone(),
+            Fields::from([("note".to_string(), "read\nagain".to_string())]),
+        );
+        let columns: Vec<String> = ["key", "title", "year", "note", "journal", "missing"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(
+            to_csv(&[&paper], &custom, &columns),
+            "key,title,year,note,journal,missing\n\
+             doe2020,\"Things, and Stuff\",2020,\"read\nagain\",\"Journal of \"\"Things\"\"\",\n"
+        );
+    }
+}
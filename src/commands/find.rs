@@ -0,0 +1,182 @@
+use crate::base::{find_paper, fit_string_to_length, load_papers, Paper};
+use crate::blog;
+use crate::embedding::{
+    encode_many_with, encode_query, load_pages, load_vectors, pdf_pages, save_pages, similarities,
+    text_model, PageVectors,
+};
+use crate::history;
+use crate::ranking::{fuzzy_nearest, top_k};
+use crate::utils::io::read_config_file;
+use anyhow::{anyhow, bail, Result};
+use std::cmp::Reverse;
+use std::fs;
+
+/// Papers listed when no number is given.
+const MAX_PAPERS: usize = 10;
+/// Pages searched through when no number is given.
+const MAX_PAGES: usize = 3;
+/// Longest quote printed for a page range.
+const QUOTE_LENGTH: usize = 240;
+
+pub fn find(query: String, key: Option<String>, max: Option<usize>) -> Result<()> {
+    if query.trim().is_empty() {
+        bail!("Nothing to find, give a question or topic");
+    }
+    match key {
+        Some(key) => find_in(&key, &query, max.unwrap_or(MAX_PAGES)),
+        None => find_papers(&query, max.unwrap_or(MAX_PAPERS)),
+    }
+}
+
+/// Closest papers of the current stack.
+fn find_papers(query: &str, k: usize) -> Result<()> {
+    let config = read_config_file()?;
+    let current = config.current_stack();
+    let papers = load_papers()?;
+    let points = load_vectors()?;
+    let ids: Vec<String> = papers
+        .values()
+        .filter(|paper| {
+            current
+                .as_ref()
+                .is_none_or(|stack| paper.stack.contains(stack))
+        })
+        .map(|paper| paper.id.clone())
+        .collect();
+    let (width, _) = termion::terminal_size()?;
+    let found: Vec<(String, f32)> = if config.features.embeddings {
+        let coords = encode_query(query)?;
+        top_k(similarities(&coords, &points, &ids), k)
+            .into_iter()
+            .map(|(id, score)| (id.clone(), score))
+            .collect()
+    } else {
+        fuzzy_nearest(query, &papers, &ids, k)
+    };
+    for (id, _) in found.iter() {
+        if let Some(paper) = papers.get(id) {
+            println!("{}", paper.display(width));
+        }
+    }
+    history::record(query, &found)
+}
+
+/// Pages of a single paper that discuss the query, with a quote from each.
+fn find_in(key: &str, query: &str, k: usize) -> Result<()> {
+    read_config_file()?.features.require_deep_find()?;
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, key).ok_or(anyhow!("No paper {}", key))?;
+    let coords = encode_query(query)?;
+    let pages = paper_pages(paper, coords.len())?;
+
+    let pages_scored = pages
+        .coords
+        .iter()
+        .map(|page| dotzilla::dot(&coords, page))
+        .enumerate();
+    let mut scored = top_k(pages_scored, k);
+    scored.sort_by_key(|(page, _)| *page);
+
+    for range in page_ranges(&scored) {
+        let (best, score) = range
+            .iter()
+            .copied()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        let first = range[0].0 + 1;
+        let last = range[range.len() - 1].0 + 1;
+        let label = match first == last {
+            true => format!("p. {}", first),
+            false => format!("pp. {}-{}", first, last),
+        };
+        println!("{:<10} {:.2}", label, score);
+        if let Some(quote) = best_sentence(&pages.pages[best], query) {
+            println!("  \"{}\"\n", fit_string_to_length(&quote, QUOTE_LENGTH));
+        }
+    }
+    Ok(())
+}
+
+/// Pages of the paper and their embeddings, which are computed and cached
+/// the first time or when they come from another model.
+pub fn paper_pages(paper: &Paper, dim: usize) -> Result<PageVectors> {
+    let mut cache = load_pages()?;
+    let cached = cache
+        .remove(&paper.id)
+        .filter(|pages| pages.coords.first().is_none_or(|c| c.len() == dim));
+    if let Some(pages) = cached {
+        return Ok(pages);
+    }
+    let bytes = fs::read(paper.pdf_location()?).map_err(|_| anyhow!("No pdf for {}", paper.id))?;
+    blog!("Extracting", "pages of {}", paper.id);
+    let pages = pdf_pages(&bytes)?;
+    blog!("Embedding", "{} pages", pages.len());
+    let model = text_model()?;
+    let coords = encode_many_with(&model, &pages)?;
+    cache.insert(paper.id.clone(), PageVectors { pages, coords });
+    save_pages(&cache)?;
+    Ok(cache.remove(&paper.id).unwrap())
+}
+
+/// Groups scored pages, sorted by page, into runs of consecutive pages.
+fn page_ranges(scored: &[(usize, f32)]) -> Vec<Vec<(usize, f32)>> {
+    let mut ranges: Vec<Vec<(usize, f32)>> = Vec::new();
+    for &(page, score) in scored {
+        match ranges.last_mut() {
+            Some(range) if range[range.len() - 1].0 + 1 == page => range.push((page, score)),
+            _ => ranges.push(vec![(page, score)]),
+        }
+    }
+    ranges
+}
+
+/// Longest of `sentences` worth quoting or keeping as a claim, longer runs
+/// are extraction accidents rather than sentences.
+pub const MAX_SENTENCE_WORDS: usize = 80;
+
+/// Sentences of a page with line breaks and hyphenation undone, skipping
+/// fragments such as headers and page numbers.
+pub fn sentences(page: &str) -> Vec<String> {
+    let mut text = String::new();
+    let mut joined = true;
+    for word in page.split_whitespace() {
+        if !joined {
+            text.push(' ');
+        }
+        // A word broken over two lines carries on without the hyphen
+        match word.strip_suffix('-') {
+            Some(stem) if stem.ends_with(char::is_alphabetic) => {
+                text.push_str(stem);
+                joined = true;
+            }
+            _ => {
+                text.push_str(word);
+                joined = false;
+            }
+        }
+    }
+    text.split_inclusive(". ")
+        .map(str::trim)
+        .filter(|sentence| sentence.split_whitespace().count() > 4)
+        .map(String::from)
+        .collect()
+}
+
+/// Sentence of the page sharing the most words with the query.
+fn best_sentence(page: &str, query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 3)
+        .map(|word| word.to_lowercase())
+        .collect();
+    sentences(page)
+        .into_iter()
+        .map(|sentence| {
+            let lower = sentence.to_lowercase();
+            let hits = terms.iter().filter(|term| lower.contains(*term)).count();
+            (hits, sentence)
+        })
+        // The first of equally good sentences wins
+        .min_by_key(|(hits, _)| Reverse(*hits))
+        .map(|(_, sentence)| sentence.to_string())
+}
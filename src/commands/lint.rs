@@ -0,0 +1,133 @@
+use crate::base::{load_papers, Paper};
+use crate::fields::load_fields;
+use crate::parser::doi::doi2bib;
+use crate::utils::collate::collation_key;
+use crate::{blog, warn};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+/// Fields a journal article is expected to have.
+const ARTICLE_FIELDS: [&str; 2] = ["volume", "pages"];
+
+struct Issue {
+    key: String,
+    problem: String,
+    fix: Option<String>,
+}
+
+/// Keys that only differ in case, accents or punctuation.
+fn normalize_key(key: &str) -> String {
+    collation_key(key)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Reports duplicates under different keys or dois.
+fn duplicates<'a, F>(papers: &[&'a Paper], what: &str, group: F) -> Vec<Issue>
+where
+    F: Fn(&Paper) -> Option<String>,
+{
+    let mut groups: BTreeMap<String, Vec<&'a Paper>> = BTreeMap::new();
+    for paper in papers {
+        if let Some(value) = group(paper) {
+            groups.entry(value).or_default().push(paper);
+        }
+    }
+    let mut issues = Vec::new();
+    for group in groups.values().filter(|group| group.len() > 1) {
+        let keys: Vec<&str> = group.iter().map(|paper| paper.id.as_str()).collect();
+        for paper in group {
+            issues.push(Issue {
+                key: paper.id.clone(),
+                problem: format!("same {} as {}", what, keys.join(", ")),
+                fix: Some(format!("bib show {}", paper.id)),
+            });
+        }
+    }
+    issues
+}
+
+/// Checks every stored entry for duplicates and missing article fields.
+/// With `online` the year, volume and pages are compared with doi.org.
+/// Fails when there are issues, so bib exits non zero.
+pub fn lint_library(online: bool) -> Result<()> {
+    let papers = load_papers()?;
+    let custom = load_fields()?;
+    let all: Vec<&Paper> = papers.values().collect();
+    let mut issues = duplicates(&all, "key", |paper| Some(normalize_key(&paper.id)));
+    issues.extend(duplicates(&all, "doi", |paper| paper.doi()));
+
+    if online {
+        blog!("Checking", "metadata of the papers with a doi on doi.org");
+    }
+    for paper in papers.values() {
+        let published = match paper.doi() {
+            Some(doi) if online => {
+                match doi2bib(&doi).and_then(|bibtex| Paper::from_bibtex(&bibtex)) {
+                    Ok(published) => Some(published),
+                    Err(err) => {
+                        warn!("Failed", "{}: {}", paper.id, err);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        if let Some(published) = &published {
+            if published.year != paper.year {
+                issues.push(Issue {
+                    key: paper.id.clone(),
+                    problem: format!("year {} but doi.org says {}", paper.year, published.year),
+                    fix: None,
+                });
+            }
+        }
+        if paper.kind != "article" {
+            continue;
+        }
+        for field in ARTICLE_FIELDS {
+            let set = paper.bibtex_field(field).is_some()
+                || custom
+                    .get(&paper.id)
+                    .is_some_and(|fields| fields.contains_key(field));
+            if set {
+                continue;
+            }
+            let value = published
+                .as_ref()
+                .and_then(|published| published.bibtex_field(field))
+                .unwrap_or(format!("<{}>", field));
+            issues.push(Issue {
+                key: paper.id.clone(),
+                problem: format!("journal article without {}", field),
+                fix: Some(format!("bib edit {} --field {}={}", paper.id, field, value)),
+            });
+        }
+    }
+
+    for issue in issues.iter() {
+        warn!("Lint", "{}: {}", issue.key, issue.problem);
+    }
+    let fixes: Vec<&String> = issues
+        .iter()
+        .filter_map(|issue| issue.fix.as_ref())
+        .collect();
+    if !fixes.is_empty() {
+        println!("\nFix-it:");
+        for fix in fixes {
+            println!("  {}", fix);
+        }
+        println!();
+    }
+    blog!(
+        "Checked",
+        "{} papers, {} issues",
+        papers.len(),
+        issues.len()
+    );
+    if !issues.is_empty() {
+        bail!("The library has {} issues", issues.len())
+    }
+    Ok(())
+}
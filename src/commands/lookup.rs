@@ -0,0 +1,35 @@
+use crate::base::{find_paper, load_papers, Paper};
+use crate::utils::io::read_config_file;
+use anyhow::{anyhow, Result};
+
+fn matches_query(paper: &Paper, query: &str) -> bool {
+    [&paper.id, &paper.title, &paper.author]
+        .iter()
+        .any(|text| text.to_lowercase().contains(query))
+}
+
+/// Fails, so bib exits with a non zero status, when the key, doi or doi
+/// alias is not in the library.
+pub fn exists(key: String) -> Result<()> {
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, &key).ok_or(anyhow!("No paper {}", key))?;
+    println!("{}", paper.id);
+    Ok(())
+}
+
+pub fn count(query: String) -> Result<()> {
+    let config = read_config_file()?;
+    let current = config.current_stack();
+    let papers = load_papers()?;
+    let query = query.to_lowercase();
+    let n_refs = papers
+        .values()
+        .filter(|paper| match &current {
+            Some(stack) => paper.stack.contains(stack),
+            None => true,
+        })
+        .filter(|paper| matches_query(paper, &query))
+        .count();
+    println!("{}", n_refs);
+    Ok(())
+}
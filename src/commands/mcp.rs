@@ -0,0 +1,164 @@
+use crate::base::{find_paper, load_papers};
+use crate::embedding::{encode_query, k_nearest, load_vectors, pdf_text};
+use crate::fields::{self, load_fields};
+use crate::ranking::fuzzy_nearest;
+use crate::utils::io::read_config_file;
+use anyhow::{anyhow, Result};
+use gag::Gag;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+/// Longest paper text handed to a client, in characters.
+const MAX_TEXT: usize = 200_000;
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search_library",
+            "description": "Semantic search over the papers in the bib library. Returns keys, authors, years and titles.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "What the papers should be about"},
+                    "limit": {"type": "integer", "description": "Maximum number of papers, 10 by default"}
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "get_bibtex",
+            "description": "BibTeX entry of a paper in the library, including custom fields.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"key": {"type": "string", "description": "Citation key, doi or doi alias"}},
+                "required": ["key"]
+            }
+        },
+        {
+            "name": "read_paper",
+            "description": "Full text of a paper's pdf, to answer questions about its content.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"key": {"type": "string", "description": "Citation key, doi or doi alias"}},
+                "required": ["key"]
+            }
+        }
+    ])
+}
+
+fn argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str> {
+    arguments[name]
+        .as_str()
+        .ok_or(anyhow!("Missing argument {}", name))
+}
+
+fn search_library(arguments: &Value) -> Result<String> {
+    let query = argument(arguments, "query")?;
+    let limit = arguments["limit"].as_u64().unwrap_or(10) as usize;
+    let papers = load_papers()?;
+    let points = load_vectors()?;
+    let ids: Vec<String> = papers.keys().cloned().collect();
+    let found = if read_config_file()?.features.embeddings {
+        // Model downloads and warnings must not end up in the protocol stream
+        let _print_gag = Gag::stdout()?;
+        k_nearest(&encode_query(query)?, &points, &ids, limit)
+    } else {
+        fuzzy_nearest(query, &papers, &ids, limit)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    };
+    Ok(found
+        .iter()
+        .filter_map(|key| papers.get(key))
+        .map(|paper| {
+            format!(
+                "{}: {} ({}) {}",
+                paper.id, paper.author, paper.year, paper.title
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn get_bibtex(arguments: &Value) -> Result<String> {
+    let key = argument(arguments, "key")?;
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, key).ok_or(anyhow!("No paper {}", key))?;
+    let custom = load_fields()?;
+    Ok(fields::with_fields(&paper.bibtex, custom.get(&paper.id)))
+}
+
+fn read_paper(arguments: &Value) -> Result<String> {
+    let key = argument(arguments, "key")?;
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, key).ok_or(anyhow!("No paper {}", key))?;
+    let bytes = fs::read(paper.pdf_location()?)?;
+    Ok(pdf_text(bytes)?.chars().take(MAX_TEXT).collect())
+}
+
+fn call_tool(params: &Value) -> Value {
+    let arguments = &params["arguments"];
+    let result = match params["name"].as_str() {
+        Some("search_library") => search_library(arguments),
+        Some("get_bibtex") => get_bibtex(arguments),
+        Some("read_paper") => read_paper(arguments),
+        _ => Err(anyhow!("Unknown tool")),
+    };
+    let (text, is_error) = match result {
+        Ok(text) => (text, false),
+        Err(err) => (err.to_string(), true),
+    };
+    json!({"content": [{"type": "text", "text": text}], "isError": is_error})
+}
+
+fn handle(method: &str, params: &Value) -> Result<Value, (i64, String)> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {"tools": {}},
+            "serverInfo": {"name": "bib", "version": env!("CARGO_PKG_VERSION")}
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({"tools": tools()})),
+        "tools/call" => Ok(call_tool(params)),
+        _ => Err((-32601, format!("Method {} not found", method))),
+    }
+}
+
+/// Serves the library as Model Context Protocol tools over stdio.
+pub fn serve() -> Result<()> {
+    let mut stdout = io::stdout();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": -32700, "message": err.to_string()}
+            }),
+            // Notifications carry no id and get no answer
+            Ok(request) => match request.get("id") {
+                None => continue,
+                Some(id) => {
+                    match handle(request["method"].as_str().unwrap_or(""), &request["params"]) {
+                        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                        Err((code, message)) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {"code": code, "message": message}
+                        }),
+                    }
+                }
+            },
+        };
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
@@ -1,4 +1,28 @@
 pub mod add;
+pub mod annotations;
+pub mod backup;
+pub mod card;
+pub mod check;
+pub mod claims;
+pub mod cv;
+pub mod deadlines;
+pub mod demo;
+pub mod doctor;
 pub mod export;
+pub mod find;
+pub mod lint;
+pub mod lookup;
+pub mod mcp;
+pub mod oa;
 pub mod prompt;
+pub mod quotes;
+pub mod reading_list;
+pub mod reembed;
+pub mod rekey;
+pub mod remove;
+pub mod reset;
+pub mod sed;
+pub mod shared;
+pub mod split;
 pub mod stack;
+pub mod stats;
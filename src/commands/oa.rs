@@ -0,0 +1,90 @@
+use crate::base::load_papers;
+use crate::parser::sherpa;
+use crate::parser::unpaywall::{self, load_cache, save_cache};
+use crate::utils::fmt::{paint, reset, Tone};
+use crate::utils::io::read_config_file;
+use crate::{blog, warn};
+use anyhow::{anyhow, Result};
+
+/// Prints the open access status of every paper in the stack with a doi,
+/// and the self-archiving policy of its journal when a Sherpa key is set.
+/// Lookups are cached, use `refresh` to query Unpaywall and Sherpa again.
+pub fn status(refresh: bool) -> Result<()> {
+    let config = read_config_file()?;
+    let current = config.current_stack();
+    let papers = load_papers()?;
+    let mut cache = load_cache()?;
+    let mut email = None;
+    let mut checked = 0;
+
+    for paper in papers.values() {
+        if let Some(stack) = &current {
+            if !paper.stack.contains(stack) {
+                continue;
+            }
+        }
+        let doi = match paper.doi() {
+            Some(doi) => doi,
+            None => continue,
+        };
+        if refresh || !cache.contains_key(&doi) {
+            // Only demand the email once there is something to look up
+            let email = match &email {
+                Some(email) => email,
+                None => email.insert(config.email.clone().ok_or(anyhow!(
+                    "Unpaywall needs a contact email, set email in ~/.bib/config.toml"
+                ))?),
+            };
+            match unpaywall::fetch(&doi, email) {
+                Ok(Some(mut status)) => {
+                    if let (Some(key), Some(issn)) = (&config.sherpa_key, &status.issn) {
+                        match sherpa::policy(issn, key) {
+                            Ok(policy) => status.policy = policy,
+                            Err(err) => warn!("Failed", "policy of {}: {}", paper.id, err),
+                        }
+                    }
+                    cache.insert(doi.clone(), status);
+                }
+                Ok(None) => {
+                    warn!("Unknown", "{} has no Unpaywall record", paper.id);
+                    continue;
+                }
+                Err(err) => {
+                    warn!("Failed", "{}: {}", paper.id, err);
+                    continue;
+                }
+            }
+        }
+        checked += 1;
+        let status = &cache[&doi];
+        let tone = if status.is_oa { Tone::Ok } else { Tone::Err };
+        println!(
+            "{}{:>8}{} {} {}{}{}",
+            paint(tone),
+            status.status,
+            reset(),
+            paper.id,
+            paint(Tone::Muted),
+            [
+                &status.version,
+                &status.license,
+                &status.policy,
+                &status.url
+            ]
+            .iter()
+            .filter_map(|field| field.as_deref())
+            .collect::<Vec<_>>()
+            .join(" • "),
+            reset(),
+        );
+    }
+    save_cache(&cache)?;
+    blog!("Checked", "{} dois", checked);
+    if config.sherpa_key.is_none() {
+        warn!(
+            "Policies",
+            "not reported, set sherpa_key in ~/.bib/config.toml to look them up"
+        );
+    }
+    Ok(())
+}
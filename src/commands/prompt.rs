@@ -1,28 +1,56 @@
-use crate::base::{save_papers, Paper};
+use crate::access::{self, Action};
+use crate::annotations::load_annotations;
+use crate::base::{find_paper, save_papers, Paper};
 use crate::blog;
 use crate::embedding::Point;
+use crate::fields::{self, load_fields, save_fields};
+use crate::history::load_history;
+use crate::ranking::{fuzzy_nearest, fuzzy_score, rank, top_k};
+use crate::revisions::load_revisions;
 use crate::stacks::Stack;
+use crate::utils::collate::collation_key;
+use crate::utils::fmt::date;
 use crate::{
-    base::load_papers,
-    embedding::{encode, k_nearest, load_vectors},
-    utils::io::read_config_file,
+    base::{load_papers, load_shared_papers},
+    embedding::{
+        cached_encode, encode_query, encode_with, k_nearest, load_shared_vectors, load_vectors,
+        similarities, text_model,
+    },
+    utils::io::{read_config_file, read_only, require_writable, save_config_file},
 };
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use copypasta::{ClipboardContext, ClipboardProvider};
 use indexmap::IndexMap;
 use std::cmp;
 use std::collections::BTreeMap;
 use std::io::{self, Stdout, Write};
+use std::thread;
+use std::time::Duration;
 use termion::event::Key;
 use termion::input::TermRead;
 use termion::raw::{IntoRawMode, RawTerminal};
 
+/// Searches listed by `show` that brought the paper up.
+const SHOWN_MATCHES: usize = 5;
+/// How many of the closest papers are ranked, as a multiple of those shown.
+const CANDIDATES: usize = 4;
+
 fn pull_up(map: &mut IndexMap<String, Paper>, key: &str) {
     if let Some(removed_paper) = map.shift_remove(key) {
         map.shift_insert(0, key.to_string(), removed_paper);
     }
 }
 
+/// Moves a paper that was just used to the front of the library, unless the
+/// library is read-only, where opening and yanking still work.
+fn mark_used(papers: &mut IndexMap<String, Paper>, key: &str) -> Result<()> {
+    if read_only() {
+        return Ok(());
+    }
+    pull_up(papers, key);
+    save_papers(papers)
+}
+
 fn filter_by_stack(papers: &IndexMap<String, Paper>) -> Result<Vec<String>> {
     let config = read_config_file()?;
     let indicies: Vec<String> = match config.current_stack() {
@@ -37,16 +65,48 @@ fn filter_by_stack(papers: &IndexMap<String, Paper>) -> Result<Vec<String>> {
     Ok(indicies)
 }
 
+/// Best papers for the query by the configured ranking, which by default
+/// means the closest ones. Without embeddings the words are matched instead.
 fn filter_by_query(
     query: String,
+    papers: &IndexMap<String, Paper>,
     points: &BTreeMap<String, Point>,
-    indicies: &Vec<String>,
+    indicies: &[String],
     k: usize,
 ) -> Result<Vec<String>> {
-    let query = encode(&query)?;
-    Ok(k_nearest(&query, points, indicies, k))
+    let config = read_config_file()?;
+    // Only the closest papers can rank high enough to be shown
+    let similarity: BTreeMap<String, f32> = if config.features.embeddings {
+        let query = encode_query(&query)?;
+        top_k(similarities(&query, points, indicies), k * CANDIDATES)
+            .into_iter()
+            .map(|(id, score)| (id.clone(), score))
+            .collect()
+    } else {
+        fuzzy_nearest(&query, papers, indicies, k * CANDIDATES)
+            .into_iter()
+            .collect()
+    };
+    // In library order, so that ties keep it
+    let found: Vec<String> = indicies
+        .iter()
+        .filter(|id| similarity.contains_key(*id))
+        .cloned()
+        .collect();
+    let mut ranked = rank(&found, papers, &similarity, &config.mine, &config.ranking);
+    ranked.truncate(k);
+    Ok(ranked)
+}
+
+fn copy_to_clipboard(text: String) -> Result<()> {
+    let mut ctx = ClipboardContext::new()
+        .map_err(|e| anyhow!("Failed to create clipboard context: {}", e))?;
+    ctx.set_contents(text)
+        .map_err(|e| anyhow!("Failed to set clipboard contents: {}", e))
 }
 
+/// Lets the user pick a paper. Besides selecting with enter,
+/// `P` copies the pdf path and `u` the doi or url of the highlighted paper.
 fn prompt_select(papers: &[Paper]) -> Result<Option<usize>> {
     let stdin = io::stdin();
     let mut stdout = io::stdout().into_raw_mode().unwrap();
@@ -56,6 +116,7 @@ fn prompt_select(papers: &[Paper]) -> Result<Option<usize>> {
     //hide cursor
     write!(stdout, "{}", termion::cursor::Hide)?;
     let mut current_index = 0;
+    let mut copied: Option<(&str, Result<String>)> = None;
     draw_ui(&mut stdout, current_index, papers, width)?;
 
     for c in stdin.keys() {
@@ -76,6 +137,16 @@ fn prompt_select(papers: &[Paper]) -> Result<Option<usize>> {
                 selected_index = None;
                 break;
             }
+            Key::Char('P') => {
+                let path = papers[current_index].pdf_location();
+                copied = Some(("pdf path", path.map(|p| p.display().to_string())));
+                break;
+            }
+            Key::Char('u') => {
+                let link = papers[current_index].link();
+                copied = Some(("link", link.ok_or(anyhow!("Paper has no doi or url"))));
+                break;
+            }
             _ => {}
         }
     }
@@ -86,6 +157,11 @@ fn prompt_select(papers: &[Paper]) -> Result<Option<usize>> {
         termion::clear::AfterCursor,
         termion::cursor::Show
     )?;
+    drop(stdout);
+    if let Some((what, text)) = copied {
+        copy_to_clipboard(text?)?;
+        blog!("Copied", "{} to clipboard", what);
+    }
     Ok(selected_index)
 }
 
@@ -96,21 +172,46 @@ fn draw_ui(
     width: u16,
 ) -> Result<()> {
     // Move the cursor to the first line of the UI
+    let mut lines = 0;
     for (i, word) in items.iter().enumerate() {
         let prefix = if i == current_index { "* " } else { "  " };
-        writeln!(stdout, "{}{}\r", prefix, word.display(width - 2))?;
+        // Wrapped titles continue under the prefix
+        let display = word.display(width - 2).replace('\n', "\r\n  ");
+        lines += 1 + display.matches('\n').count();
+        writeln!(stdout, "{}{}\r", prefix, display)?;
     }
-    write!(stdout, "{}", termion::cursor::Up(items.len() as u16))?;
+    write!(stdout, "{}", termion::cursor::Up(lines as u16))?;
     stdout.flush()?;
     Ok(())
 }
 
-pub fn list(max: Option<usize>) -> Result<()> {
+fn parse_filters(filters: &[String]) -> Result<Vec<(String, String)>> {
+    filters
+        .iter()
+        .map(|f| fields::parse_assignment(f))
+        .collect()
+}
+
+pub fn list(max: Option<usize>, filters: Vec<String>, sort: String) -> Result<()> {
     //Loading bigliography
     let (width, height) = termion::terminal_size()?;
     let papers = load_papers()?;
-    let n_refs = papers.len();
-    let indicies = filter_by_stack(&papers)?;
+    let custom = load_fields()?;
+    let filters = parse_filters(&filters)?;
+    let mut indicies = filter_by_stack(&papers)?;
+    indicies.retain(|key| fields::matches(custom.get(key), &filters));
+    match sort.as_str() {
+        "rank" => {
+            let config = read_config_file()?;
+            let none = BTreeMap::new();
+            indicies = rank(&indicies, &papers, &none, &config.mine, &config.ranking);
+        }
+        "author" => indicies.sort_by_cached_key(|key| collation_key(&papers[key].first_author())),
+        "title" => indicies.sort_by_cached_key(|key| collation_key(&papers[key].title)),
+        "year" => indicies.sort_by_key(|key| cmp::Reverse(papers[key].year)),
+        _ => (),
+    }
+    let n_refs = indicies.len();
     // Determine the maximum number of entries to display
     let max_entries = match max {
         Some(m) => cmp::min(m, n_refs),
@@ -134,12 +235,29 @@ pub fn list(max: Option<usize>) -> Result<()> {
     Ok(())
 }
 
-fn select(query: String, papers: &IndexMap<String, Paper>) -> Result<Option<Paper>> {
-    let points = load_vectors()?;
+/// Adds the papers of the shared library that are not in the local one.
+fn overlay_shared(
+    papers: &mut IndexMap<String, Paper>,
+    points: &mut BTreeMap<String, Point>,
+) -> Result<()> {
+    for (key, paper) in load_shared_papers()? {
+        papers.entry(key).or_insert(paper);
+    }
+    for (key, point) in load_shared_vectors()? {
+        points.entry(key).or_insert(point);
+    }
+    Ok(())
+}
+
+pub fn select(query: String, papers: &IndexMap<String, Paper>) -> Result<Option<Paper>> {
+    let mut points = load_vectors()?;
+    let mut papers = papers.clone();
+    overlay_shared(&mut papers, &mut points)?;
+    let papers = &papers;
     let (_width, height) = termion::terminal_size()?;
     let mut indicies = filter_by_stack(papers)?;
     if query.len() > 0 {
-        indicies = filter_by_query(query, &points, &indicies, height as usize - 10)?;
+        indicies = filter_by_query(query, papers, &points, &indicies, height as usize - 10)?;
     };
     let items: Vec<Paper> = indicies
         .iter()
@@ -152,13 +270,22 @@ fn select(query: String, papers: &IndexMap<String, Paper>) -> Result<Option<Pape
     Ok(paper)
 }
 
+/// The paper with the exact key, doi or doi alias, otherwise the one picked
+/// from the matches, so fix-its like `bib edit <key>` need no picking.
+pub fn resolve(query: String, papers: &IndexMap<String, Paper>) -> Result<Option<Paper>> {
+    match find_paper(papers, &query) {
+        Some(paper) => Ok(Some(paper.clone())),
+        None => select(query, papers),
+    }
+}
+
 pub fn open(query: String) -> Result<()> {
     let mut papers = load_papers()?;
     match select(query, &papers)? {
         Some(paper) => {
             paper.open_pdf()?;
-            pull_up(&mut papers, &paper.id);
-            save_papers(&papers)?;
+            access::record(&paper.id, Action::Open)?;
+            mark_used(&mut papers, &paper.id)?;
         }
         None => (),
     };
@@ -169,12 +296,9 @@ pub fn yank(query: String) -> Result<()> {
     let mut papers = load_papers()?;
     match select(query, &papers)? {
         Some(paper) => {
-            let mut ctx = ClipboardContext::new()
-                .map_err(|e| anyhow!("Failed to create clipboard context: {}", e))?;
-            ctx.set_contents(paper.bibtex.clone())
-                .map_err(|e| anyhow!("Failed to set clipboard contents: {}", e))?;
-            pull_up(&mut papers, &paper.id);
-            save_papers(&papers)?;
+            copy_to_clipboard(paper.bibtex.clone())?;
+            access::record(&paper.id, Action::Yank)?;
+            mark_used(&mut papers, &paper.id)?;
             blog!("Copied", "bibtex to clipboard")
         }
         None => (),
@@ -182,6 +306,217 @@ pub fn yank(query: String) -> Result<()> {
     Ok(())
 }
 
+pub fn show(query: String) -> Result<()> {
+    let papers = load_papers()?;
+    if let Some(paper) = resolve(query, &papers)? {
+        let (width, _) = termion::terminal_size()?;
+        let custom = load_fields()?;
+        println!("{}\n", paper.display(width));
+        if let Some(alias) = paper.alias() {
+            println!("alias: {}\n", alias);
+        }
+        // Latest searches that found the paper, for why it is here
+        let history = load_history()?;
+        let matches = history.get(&paper.id).map_or(&[][..], |m| m.as_slice());
+        for found in matches.iter().rev().take(SHOWN_MATCHES) {
+            println!(
+                "matched: '{}' {:.2} on {}",
+                found.query,
+                found.score,
+                date(found.at)
+            );
+        }
+        if !matches.is_empty() {
+            println!();
+        }
+        let annotations = load_annotations()?;
+        if let Some(layers) = annotations.get(&paper.id) {
+            for layer in layers {
+                println!("annotated: by {} on {}", layer.author, date(layer.imported));
+            }
+            println!();
+        }
+        let revisions = load_revisions()?;
+        if let Some(revisions) = revisions.get(&paper.id) {
+            for revision in revisions {
+                println!("revised: by {} on {}", revision.by, date(revision.at));
+            }
+            println!();
+        }
+        println!(
+            "{}",
+            fields::with_fields(&paper.bibtex, custom.get(&paper.id))
+        );
+    }
+    Ok(())
+}
+
+pub fn edit(query: String, assignments: Vec<String>) -> Result<()> {
+    if assignments.is_empty() {
+        bail!("Nothing to edit, use --field key=value")
+    }
+    require_writable()?;
+    let assignments = parse_filters(&assignments)?;
+    let papers = load_papers()?;
+    let paper = match resolve(query, &papers)? {
+        Some(paper) => paper,
+        None => return Ok(()),
+    };
+    if paper.shared {
+        bail!(
+            "{} is in the shared library, run bib copy-to-local {} first",
+            paper.id,
+            paper.id
+        )
+    }
+    let mut custom = load_fields()?;
+    let fields = custom.entry(paper.id.clone()).or_default();
+    for (key, value) in assignments {
+        if value.is_empty() {
+            fields.remove(&key);
+            blog!("Removed", "field {}", key);
+            continue;
+        }
+        if paper.bibtex_field(&key).is_some() {
+            bail!("Field {} is already part of the bibtex", key)
+        }
+        blog!("Set", "{} = {}", key, value);
+        fields.insert(key, value);
+    }
+    if fields.is_empty() {
+        custom.remove(&paper.id);
+    }
+    save_fields(&custom)
+}
+
+/// Score below which `open_first` asks before opening.
+const OPEN_THRESHOLD: f32 = 0.85;
+
+/// Opens the best fuzzy match straight away, asking first when the match is
+/// below the configured threshold.
+pub fn open_first(query: String) -> Result<()> {
+    if query.trim().is_empty() {
+        bail!("Give part of a title, key or author to open")
+    }
+    let config = read_config_file()?;
+    let mut papers = load_papers()?;
+    let best = filter_by_stack(&papers)?
+        .iter()
+        .filter_map(|key| papers.get(key))
+        .map(|paper| (fuzzy_score(&query, paper), paper))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(score, paper)| (score, paper.clone()));
+    let (score, paper) = match best {
+        Some(best) => best,
+        None => bail!("No papers to open"),
+    };
+    let (width, _) = termion::terminal_size()?;
+    println!("{}", paper.display(width));
+    if score < config.open_threshold.unwrap_or(OPEN_THRESHOLD) {
+        print!("{:>12} open it? [y/N] ", format!("{:.2} match", score));
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y") {
+            return Ok(());
+        }
+    }
+    paper.open_pdf()?;
+    access::record(&paper.id, Action::Open)?;
+    mark_used(&mut papers, &paper.id)
+}
+
+/// Marks or unmarks a paper as authored by the user.
+pub fn mine(query: String) -> Result<()> {
+    require_writable()?;
+    let papers = load_papers()?;
+    let paper = match resolve(query, &papers)? {
+        Some(paper) => paper,
+        None => return Ok(()),
+    };
+    if paper.shared {
+        bail!(
+            "{} is in the shared library, run bib copy-to-local {} first",
+            paper.id,
+            paper.id
+        )
+    }
+    let mut config = read_config_file()?;
+    match config.mine.iter().position(|key| *key == paper.id) {
+        Some(index) => {
+            config.mine.remove(index);
+            blog!("Unmarked", "{} as yours", paper.id);
+        }
+        None => {
+            config.mine.push(paper.id.clone());
+            blog!("Marked", "{} as yours", paper.id);
+        }
+    }
+    save_config_file(&config)
+}
+
+/// Clipboard contents worth searching for: a single title or quoted claim.
+fn looks_like_title(text: &str) -> bool {
+    let text = text.trim();
+    let words = text.split_whitespace().count();
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    !text.contains('\n')
+        && (3..=60).contains(&words)
+        && letters * 2 > text.len()
+        && !text.starts_with("http")
+        && !text.starts_with("\\cite")
+        && !text.starts_with("[@")
+}
+
+/// Watches the clipboard for titles and claims and swaps them for a citation of the chosen paper.
+pub fn cwyw(pandoc: bool) -> Result<()> {
+    let mut ctx = ClipboardContext::new()
+        .map_err(|e| anyhow!("Failed to create clipboard context: {}", e))?;
+    read_config_file()?
+        .features
+        .require_embeddings("Cite while you write")?;
+    let model = text_model()?;
+    let mut last = ctx.get_contents().unwrap_or_default();
+    blog!(
+        "Watching",
+        "clipboard for titles and claims, ctrl-c to stop"
+    );
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let text = ctx.get_contents().unwrap_or_default();
+        if text == last {
+            continue;
+        }
+        last = text.clone();
+        if !looks_like_title(&text) {
+            continue;
+        }
+        let (_width, height) = termion::terminal_size()?;
+        let papers = load_papers()?;
+        let points = load_vectors()?;
+        let indicies = filter_by_stack(&papers)?;
+        let query = cached_encode(text.trim().trim_matches('"'), |q| encode_with(&model, q))?;
+        let items: Vec<Paper> = k_nearest(&query, &points, &indicies, height as usize / 2)
+            .iter()
+            .filter_map(|key| papers.get(key).cloned())
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        blog!("Searching", "{}", text.trim());
+        if let Some(index) = prompt_select(&items)? {
+            let cite = match pandoc {
+                true => format!("[@{}]", items[index].id),
+                false => format!("\\cite{{{}}}", items[index].id),
+            };
+            ctx.set_contents(cite.clone())
+                .map_err(|e| anyhow!("Failed to set clipboard contents: {}", e))?;
+            blog!("Copied", "{}", cite);
+            last = cite;
+        }
+    }
+}
+
 fn toggle_paper_stack(paper: &mut Paper, new_stack: &Stack) {
     let stack_index = paper.stack.iter().position(|s| s.name == new_stack.name);
     match stack_index {
@@ -208,7 +543,7 @@ pub fn toggle(stack: String, query: String) -> Result<()> {
     let (_width, height) = termion::terminal_size()?;
     let mut indicies = filter_by_stack(&papers)?;
     if query.len() > 0 {
-        indicies = filter_by_query(query, &points, &indicies, height as usize - 10)?;
+        indicies = filter_by_query(query, &papers, &points, &indicies, height as usize - 10)?;
     };
     let items: Vec<Paper> = indicies
         .iter()
@@ -0,0 +1,104 @@
+use crate::base::{find_paper, load_papers, Paper};
+use crate::commands::find::{paper_pages, sentences, MAX_SENTENCE_WORDS};
+use crate::embedding::{encode_many_with, encode_query, text_model};
+use crate::quotes::{load_quotes, same_topic, save_quotes, Quote, TopicQuotes};
+use crate::utils::clock;
+use crate::utils::io::{read_config_file, read_only};
+use crate::warn;
+use anyhow::{anyhow, bail, Result};
+use serde_json::json;
+
+/// Pages whose sentences are considered as quotes.
+const PAGES_SEARCHED: usize = 4;
+
+/// Prints verbatim quotes of a paper about a topic with the page they are on.
+/// Quotes are stored, so asking again for the same topic is immediate.
+pub fn quotes(key: String, topic: String, max: usize, json: bool, refresh: bool) -> Result<()> {
+    if topic.trim().is_empty() {
+        bail!("Give a topic to quote the paper on");
+    }
+    read_config_file()?.features.require_deep_find()?;
+    let papers = load_papers()?;
+    let paper = find_paper(&papers, &key).ok_or(anyhow!("No paper {}", key))?;
+    let mut stored = load_quotes()?;
+    let known = stored
+        .get(&paper.id)
+        .and_then(|topics| topics.iter().find(|t| same_topic(&t.topic, &topic)))
+        .filter(|found| !refresh && found.quotes.len() >= max)
+        .cloned();
+    let found = match known {
+        Some(found) => found,
+        None => {
+            let found = extract(paper, &topic, max)?;
+            let topics = stored.entry(paper.id.clone()).or_default();
+            topics.retain(|t| !same_topic(&t.topic, &topic));
+            topics.push(found.clone());
+            if !read_only() {
+                save_quotes(&stored)?;
+            }
+            found
+        }
+    };
+    let quotes: Vec<&Quote> = found.quotes.iter().take(max).collect();
+    if quotes.is_empty() {
+        warn!("Nothing", "no sentences of {} could be quoted", paper.id);
+    }
+
+    if json {
+        let quotes: Vec<_> = quotes
+            .iter()
+            .map(|quote| json!({"page": quote.page, "text": quote.text, "score": quote.score}))
+            .collect();
+        let output = json!({"key": paper.id, "topic": found.topic, "quotes": quotes});
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        for quote in quotes {
+            println!("\"{}\" ({}, p. {})\n", quote.text, paper.id, quote.page);
+        }
+    }
+    Ok(())
+}
+
+/// Ranks the sentences of the pages closest to the topic.
+fn extract(paper: &Paper, topic: &str, max: usize) -> Result<TopicQuotes> {
+    let coords = encode_query(topic)?;
+    let pages = paper_pages(paper, coords.len())?;
+    let mut closest: Vec<(usize, f32)> = pages
+        .coords
+        .iter()
+        .map(|page| dotzilla::dot(&coords, page))
+        .enumerate()
+        .collect();
+    closest.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut candidates: Vec<(usize, String)> = Vec::new();
+    for (page, _) in closest.iter().take(PAGES_SEARCHED) {
+        for sentence in sentences(&pages.pages[*page]) {
+            if sentence.split_whitespace().count() <= MAX_SENTENCE_WORDS {
+                candidates.push((page + 1, sentence));
+            }
+        }
+    }
+    let texts: Vec<String> = candidates.iter().map(|(_, text)| text.clone()).collect();
+    let scores: Vec<f32> = match texts.is_empty() {
+        true => Vec::new(),
+        false => encode_many_with(&text_model()?, &texts)?
+            .iter()
+            .map(|sentence| dotzilla::dot(&coords, sentence))
+            .collect(),
+    };
+    let mut quotes: Vec<Quote> = candidates
+        .into_iter()
+        .zip(scores)
+        .map(|((page, text), score)| Quote { text, page, score })
+        .collect();
+    quotes.sort_by(|a, b| b.score.total_cmp(&a.score));
+    quotes.truncate(max);
+    // Read in the order they appear in the paper
+    quotes.sort_by_key(|quote| quote.page);
+    Ok(TopicQuotes {
+        topic: topic.trim().to_string(),
+        quotes,
+        created: clock::now()?,
+    })
+}
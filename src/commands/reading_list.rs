@@ -0,0 +1,102 @@
+use crate::base::{load_papers, Paper};
+use crate::blog;
+use crate::commands::cv::venue;
+use crate::utils::io::read_config_file;
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Bibtex braces and line breaks do not belong in a handout.
+fn plain(text: &str) -> String {
+    text.replace(['{', '}'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn handout_item(number: usize, paper: &Paper) -> String {
+    let mut item = format!("## {}. {}\n\n", number, plain(&paper.title));
+    let mut byline = format!("*{}*", paper.full_authors().join(", "));
+    if let Some(venue) = venue(paper) {
+        byline.push_str(&format!(" — {}", plain(&venue)));
+    }
+    item.push_str(&format!("{}, {}\n\n", byline, paper.year));
+    if let Some(abstract_) = paper.bibtex_field("abstract") {
+        item.push_str(&format!("> {}\n\n", plain(&abstract_)));
+    }
+    if let Some(link) = paper.link() {
+        item.push_str(&format!("<{}>\n\n", link));
+    }
+    item
+}
+
+/// Turns the markdown into a pdf with pandoc.
+fn to_pdf(markdown: &str, output: &str) -> Result<()> {
+    let mut child = Command::new("pandoc")
+        .args(["--from", "markdown", "--output", output])
+        .arg("--variable=geometry:margin=2.5cm")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|_| anyhow!("pandoc is needed for pdf reading lists, use --format md"))?;
+    child
+        .stdin
+        .take()
+        .ok_or(anyhow!("Could not write to pandoc"))?
+        .write_all(markdown.as_bytes())?;
+    if !child.wait()?.success() {
+        bail!("pandoc could not create {}", output)
+    }
+    Ok(())
+}
+
+/// Writes a handout with the title, authors, venue, abstract and link of every
+/// paper in a stack, the current one unless given.
+pub fn reading_list(stack: Option<String>, format: String, output: Option<String>) -> Result<()> {
+    let config = read_config_file()?;
+    let stack = match stack {
+        Some(name) => Some(
+            config
+                .stacks
+                .iter()
+                .find(|s| s.name == name)
+                .cloned()
+                .ok_or(anyhow!("Stack {} does not exist", name))?,
+        ),
+        None => config.current_stack(),
+    };
+    let papers = load_papers()?;
+    let selected: Vec<&Paper> = papers
+        .values()
+        .filter(|paper| stack.as_ref().is_none_or(|s| paper.stack.contains(s)))
+        .collect();
+    if selected.is_empty() {
+        bail!("No papers to put on the reading list")
+    }
+
+    let title = match &stack {
+        Some(stack) => format!("Reading list: {}", stack.name),
+        None => "Reading list".to_string(),
+    };
+    let mut markdown = format!("# {}\n\n", title);
+    for (number, paper) in selected.iter().enumerate() {
+        markdown.push_str(&handout_item(number + 1, paper));
+    }
+
+    match (format.as_str(), output) {
+        ("pdf", output) => {
+            let name = stack.as_ref().map_or("reading-list".to_string(), |s| {
+                format!("{}-reading-list", s.name)
+            });
+            let output = output.unwrap_or(format!("{}.pdf", name));
+            to_pdf(&markdown, &output)?;
+            blog!("Saved", "{} papers to {}", selected.len(), output);
+        }
+        (_, Some(output)) => {
+            fs::write(&output, markdown)?;
+            blog!("Saved", "{} papers to {}", selected.len(), output);
+        }
+        (_, None) => print!("{}", markdown),
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use crate::base::load_papers;
+use crate::embedding::{
+    encode_with, load_vectors, load_vectors_model, model_name, save_vectors, save_vectors_model,
+    text_model, Point,
+};
+use crate::utils::io::{read_config_file, require_writable};
+use crate::{blog, warn};
+use anyhow::Result;
+use std::fs;
+
+/// Embeds again the papers whose vectors are missing or come from another
+/// model, every paper once the configured model changed.
+pub fn reembed(all: bool, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
+    read_config_file()?
+        .features
+        .require_embeddings("Reembedding")?;
+    let papers = load_papers()?;
+    let mut vectors = load_vectors()?;
+    let model = text_model()?;
+    let dim = encode_with(&model, "dimension probe")?.len();
+    // Two models can give vectors of the same size, so the size alone does
+    // not show the model changed
+    let current = model_name()?;
+    let previous = load_vectors_model()?;
+    let switched = previous.as_deref().is_some_and(|name| name != current);
+    if switched {
+        blog!(
+            "Switching",
+            "from {} to {}, every paper is embedded again",
+            previous.unwrap_or_default(),
+            current
+        );
+    }
+    let stale: Vec<_> = papers
+        .values()
+        .filter(|paper| {
+            all || switched
+                || vectors
+                    .get(&paper.id)
+                    .is_none_or(|point| point.dim() != dim)
+        })
+        .collect();
+
+    if dry_run {
+        for paper in stale.iter() {
+            blog!("Would", "embed {}", paper.id);
+        }
+        if !stale.is_empty() {
+            blog!("Would", "write ~/.bib/vectors.bin");
+        }
+        return Ok(());
+    }
+
+    let mut embedded = 0;
+    for paper in stale.iter() {
+        let bytes = match fs::read(paper.pdf_location()?) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                warn!("Missing", "no pdf for {}", paper.id);
+                continue;
+            }
+        };
+        match Point::from_bytes_with(&model, paper.id.clone(), bytes) {
+            Ok(point) => {
+                vectors.insert(paper.id.clone(), point);
+                // Saved every time so an interruption keeps the work done so far
+                save_vectors(&vectors)?;
+                embedded += 1;
+            }
+            Err(err) => warn!("Failed", "{}: {}", paper.id, err),
+        }
+    }
+    blog!("Embedded", "{} of {} papers", embedded, stale.len());
+    if embedded == stale.len() {
+        save_vectors_model(current)?;
+    } else if switched {
+        warn!(
+            "Unfinished",
+            "some papers still have vectors of the old model, run bib reembed again"
+        );
+    }
+    Ok(())
+}
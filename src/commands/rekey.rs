@@ -0,0 +1,177 @@
+use crate::access::{load_accesses, save_accesses};
+use crate::annotations::{load_annotations, save_annotations};
+use crate::base::{load_links, load_papers, save_links, save_papers};
+use crate::claims::{load_claims, save_claims};
+use crate::commands::backup::rename_backed_up;
+use crate::embedding::{load_pages, load_vectors, save_pages, save_vectors};
+use crate::fields::{load_fields, save_fields};
+use crate::history::{load_history, save_history};
+use crate::keys::{matches_key, unique_key, KeyStyle};
+use crate::quotes::{load_quotes, save_quotes};
+use crate::revisions::{load_revisions, save_revisions};
+use crate::utils::io::{pdf_path, read_config_file, require_writable, save_config_file};
+use crate::{blog, warn};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+
+/// Moves the values of renamed keys. Every old key is taken out before any
+/// new one goes in, so keys that swap places are not lost.
+pub fn rename_keys<V>(map: &mut BTreeMap<String, V>, renames: &HashMap<String, String>) -> bool {
+    let moved: Vec<(String, V)> = renames
+        .iter()
+        .filter_map(|(old, new)| map.remove(old).map(|value| (new.clone(), value)))
+        .collect();
+    let changed = !moved.is_empty();
+    map.extend(moved);
+    changed
+}
+
+/// Gives every paper a key in the style. Keys already in the style, with or
+/// without a collision suffix, are kept; the others get the first free
+/// suffix, oldest papers first. Everything stored by key follows, pdfs last.
+pub fn rekey(style: Option<String>, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
+    let mut config = read_config_file()?;
+    let style = match style {
+        Some(name) => KeyStyle::parse(&name)?,
+        None => match config.key_style {
+            Some(style) => style,
+            None => bail!("Give a --style, or set key_style in ~/.bib/config.toml"),
+        },
+    };
+    let papers = load_papers()?;
+    let mut taken = HashSet::new();
+    let mut pending = Vec::new();
+    for paper in papers.values() {
+        let key = style.key(paper);
+        if matches_key(&paper.id, &key) {
+            taken.insert(paper.id.clone());
+        } else {
+            pending.push((paper, key));
+        }
+    }
+    // Suffixes follow the papers, not their place in the library
+    pending.sort_by(|(a, _), (b, _)| a.year.cmp(&b.year).then_with(|| a.title.cmp(&b.title)));
+    let mut plan = Vec::new();
+    for (paper, key) in pending {
+        let key = unique_key(&key, |key| taken.contains(key));
+        taken.insert(key.clone());
+        plan.push((paper.id.clone(), key));
+    }
+    if plan.is_empty() {
+        blog!("Nothing", "to rekey, all keys are in style already");
+        return Ok(());
+    }
+    for (old, new) in plan.iter() {
+        blog!(
+            if dry_run { "Would" } else { "Rekeying" },
+            "{} to {}",
+            old,
+            new
+        );
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let renames: HashMap<String, String> = plan.into_iter().collect();
+
+    let papers = papers
+        .into_iter()
+        .map(|(id, paper)| match renames.get(&id) {
+            Some(key) => (key.clone(), paper.with_key(key)),
+            None => (id, paper),
+        })
+        .collect();
+    save_papers(&papers)?;
+
+    let mut vectors = load_vectors()?;
+    if rename_keys(&mut vectors, &renames) {
+        for (id, point) in std::mem::take(&mut vectors) {
+            vectors.insert(id.clone(), point.with_id(id));
+        }
+        save_vectors(&vectors)?;
+    }
+    let mut links = load_links()?;
+    let linked: HashSet<String> = links.keys().cloned().collect();
+    if rename_keys(&mut links, &renames) {
+        save_links(&links)?;
+    }
+    let mut fields = load_fields()?;
+    if rename_keys(&mut fields, &renames) {
+        save_fields(&fields)?;
+    }
+    let mut pages = load_pages()?;
+    if rename_keys(&mut pages, &renames) {
+        save_pages(&pages)?;
+    }
+    let mut quotes = load_quotes()?;
+    if rename_keys(&mut quotes, &renames) {
+        save_quotes(&quotes)?;
+    }
+    let mut claims = load_claims()?;
+    if rename_keys(&mut claims, &renames) {
+        save_claims(&claims)?;
+    }
+    let mut annotations = load_annotations()?;
+    if rename_keys(&mut annotations, &renames) {
+        save_annotations(&annotations)?;
+    }
+    let mut accesses = load_accesses()?;
+    if rename_keys(&mut accesses, &renames) {
+        save_accesses(&accesses)?;
+    }
+    let mut history = load_history()?;
+    if rename_keys(&mut history, &renames) {
+        save_history(&history)?;
+    }
+    let mut revisions = load_revisions()?;
+    if rename_keys(&mut revisions, &renames) {
+        save_revisions(&revisions)?;
+    }
+    if config.mine.iter().any(|id| renames.contains_key(id)) {
+        for id in config.mine.iter_mut() {
+            if let Some(key) = renames.get(id) {
+                *id = key.clone();
+            }
+        }
+        save_config_file(&config)?;
+    }
+    rename_backed_up(&renames)?;
+
+    // Pdfs go through temporary names, as a new key can be another's old one
+    let mut moving = Vec::new();
+    for (old, new) in renames.iter() {
+        let source = pdf_path(old)?;
+        if linked.contains(old) || !source.exists() {
+            continue;
+        }
+        let staged = pdf_path(&format!("{}.rekey", new))?;
+        fs::rename(&source, &staged).with_context(|| {
+            format!(
+                "Could not move {}, rename it to {}.pdf",
+                source.display(),
+                new
+            )
+        })?;
+        moving.push((staged, pdf_path(new)?));
+    }
+    for (staged, target) in moving {
+        fs::rename(&staged, &target).with_context(|| {
+            format!(
+                "Could not move {} to {}",
+                staged.display(),
+                target.display()
+            )
+        })?;
+    }
+
+    blog!("Rekeyed", "{} papers", renames.len());
+    warn!(
+        "Suggestion",
+        "documents citing the old keys need updating, see bib export"
+    );
+    Ok(())
+}
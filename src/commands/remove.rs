@@ -0,0 +1,146 @@
+use crate::access::{load_accesses, save_accesses};
+use crate::annotations::{load_annotations, save_annotations};
+use crate::base::{load_links, load_papers, save_links, save_papers, Paper};
+use crate::claims::{load_claims, save_claims};
+use crate::commands::prompt::resolve;
+use crate::embedding::{load_pages, load_vectors, save_pages, save_vectors};
+use crate::fields::{load_fields, save_fields};
+use crate::history::{load_history, save_history};
+use crate::quotes::{load_quotes, save_quotes};
+use crate::revisions::{load_revisions, save_revisions};
+use crate::tombstones::{load_tombstones, save_tombstones, Tombstone};
+use crate::utils::io::{self, pdf_path, read_config_file, require_writable, save_config_file};
+use crate::{blog, warn};
+use anyhow::{bail, Result};
+use std::fs;
+use std::io::Write;
+
+/// Prints the stores and files removing the paper would write or delete.
+fn print_plan(paper: &Paper) -> Result<()> {
+    let id = &paper.id;
+    let links = load_links()?;
+    let stores = [
+        (true, io::tombstones_path()?),
+        (true, io::papers_path()?),
+        (load_vectors()?.contains_key(id), io::vectors_path()?),
+        (load_fields()?.contains_key(id), io::fields_path()?),
+        (load_pages()?.contains_key(id), io::pages_path()?),
+        (load_quotes()?.contains_key(id), io::quotes_path()?),
+        (load_claims()?.contains_key(id), io::claims_path()?),
+        (
+            load_annotations()?.contains_key(id),
+            io::annotations_path()?,
+        ),
+        (load_accesses()?.contains_key(id), io::accesses_path()?),
+        (load_history()?.contains_key(id), io::history_path()?),
+        (load_revisions()?.contains_key(id), io::revisions_path()?),
+        (
+            read_config_file()?.mine.contains(id),
+            io::bib_dir()?.join("config.toml"),
+        ),
+        (links.contains_key(id), io::links_path()?),
+    ];
+    blog!("Would", "remove {}", id);
+    for (touched, path) in stores {
+        if touched {
+            blog!("Would", "write {}", path.display());
+        }
+    }
+    let pdf = pdf_path(id)?;
+    match links.get(id) {
+        Some(linked) => blog!("Would", "keep linked pdf {}", linked.display()),
+        None if pdf.exists() => blog!("Would", "delete {}", pdf.display()),
+        None => (),
+    }
+    Ok(())
+}
+
+/// Deletes a paper with its pdf and everything stored about it, leaving a
+/// tombstone so adding it again is noticed.
+pub fn remove(query: String, yes: bool, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
+    let mut papers = load_papers()?;
+    let paper = match resolve(query, &papers)? {
+        Some(paper) => paper,
+        None => return Ok(()),
+    };
+    if paper.shared {
+        bail!("{} is in the shared library, which is read-only", paper.id)
+    }
+    if dry_run {
+        return print_plan(&paper);
+    }
+    if !yes {
+        print!("{:>12} {} and its pdf? [y/N] ", "Remove", paper.id);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y") {
+            return Ok(());
+        }
+    }
+    let id = &paper.id;
+
+    let mut tombstones = load_tombstones()?;
+    tombstones.insert(id.clone(), Tombstone::of(&paper)?);
+    save_tombstones(&tombstones)?;
+
+    papers.shift_remove(id);
+    save_papers(&papers)?;
+    let mut vectors = load_vectors()?;
+    if vectors.remove(id).is_some() {
+        save_vectors(&vectors)?;
+    }
+    let mut fields = load_fields()?;
+    if fields.remove(id).is_some() {
+        save_fields(&fields)?;
+    }
+    let mut pages = load_pages()?;
+    if pages.remove(id).is_some() {
+        save_pages(&pages)?;
+    }
+    let mut quotes = load_quotes()?;
+    if quotes.remove(id).is_some() {
+        save_quotes(&quotes)?;
+    }
+    let mut claims = load_claims()?;
+    if claims.remove(id).is_some() {
+        save_claims(&claims)?;
+    }
+    let mut annotations = load_annotations()?;
+    if annotations.remove(id).is_some() {
+        save_annotations(&annotations)?;
+    }
+    let mut accesses = load_accesses()?;
+    if accesses.remove(id).is_some() {
+        save_accesses(&accesses)?;
+    }
+    let mut history = load_history()?;
+    if history.remove(id).is_some() {
+        save_history(&history)?;
+    }
+    let mut revisions = load_revisions()?;
+    if revisions.remove(id).is_some() {
+        save_revisions(&revisions)?;
+    }
+    let mut config = read_config_file()?;
+    if config.mine.contains(id) {
+        config.mine.retain(|key| key != id);
+        save_config_file(&config)?;
+    }
+
+    // Linked pdfs belong to the user's own folders and stay
+    let mut links = load_links()?;
+    if let Some(linked) = links.remove(id) {
+        save_links(&links)?;
+        warn!("Kept", "linked pdf {}", linked.display());
+    }
+    let pdf = pdf_path(id)?;
+    if pdf.exists() {
+        fs::remove_file(pdf)?;
+    }
+    blog!("Removed", "{}", paper.title);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::base::load_papers;
+use crate::embedding::load_vectors;
+use crate::utils::io::{bib_dir, read_config_file, require_writable, save_config_file};
+use crate::{blog, warn};
+use anyhow::{bail, Result};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Copies the files of the library, pdfs included, leaving out the model
+/// cache and earlier backups.
+fn backup(dir: &Path, target: &Path) -> Result<usize> {
+    fs::create_dir_all(target.join("pdfs"))?;
+    let mut copied = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            fs::copy(&path, target.join(path.file_name().unwrap()))?;
+            copied += 1;
+        }
+    }
+    let pdfs = dir.join("pdfs");
+    if pdfs.is_dir() {
+        for entry in fs::read_dir(pdfs)? {
+            let path = entry?.path();
+            if path.is_file() {
+                fs::copy(&path, target.join("pdfs").join(path.file_name().unwrap()))?;
+                copied += 1;
+            }
+        }
+    }
+    Ok(copied)
+}
+
+fn count_pdfs(dir: &Path) -> Result<usize> {
+    match dir.join("pdfs").is_dir() {
+        true => Ok(fs::read_dir(dir.join("pdfs"))?.count()),
+        false => Ok(0),
+    }
+}
+
+/// Empties the library after the user types its name, keeping stacks and
+/// settings. Everything is backed up to the library's backups first.
+pub fn reset(confirm: bool) -> Result<()> {
+    if confirm {
+        require_writable()?;
+    }
+    let dir = bib_dir()?;
+    let papers = load_papers()?.len();
+    let vectors = load_vectors()?.len();
+    let pdfs = count_pdfs(&dir)?;
+    blog!(
+        "Library",
+        "{} papers, {} embeddings and {} pdfs",
+        papers,
+        vectors,
+        pdfs
+    );
+    if !confirm {
+        warn!(
+            "Nothing",
+            "removed, run bib reset --confirm to empty the library"
+        );
+        return Ok(());
+    }
+
+    // The library's path is typed, so a BIB_HOME set by mistake shows here
+    let name = dir.display().to_string();
+    print!("Type {} to delete all of it: ", name);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if answer.trim() != name {
+        bail!("Reset cancelled, nothing was removed")
+    }
+
+    // Named by the real time, as a pinned clock would reuse the directory
+    // of an earlier reset and copy over its backup
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let target = dir.join("backups").join(stamp.to_string());
+    if target.exists() {
+        bail!("{} already exists, nothing was removed", target.display())
+    }
+    let copied = backup(&dir, &target)?;
+    blog!("Backed up", "{} files to {}", copied, target.display());
+
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "bin" || ext == "bak")
+        {
+            fs::remove_file(path)?;
+        }
+    }
+    if dir.join("pdfs").is_dir() {
+        fs::remove_dir_all(dir.join("pdfs"))?;
+    }
+    // The marks refer to papers that are gone
+    let mut config = read_config_file()?;
+    config.mine.clear();
+    save_config_file(&config)?;
+
+    blog!(
+        "Removed",
+        "{} papers, {} embeddings and {} pdfs",
+        papers,
+        vectors,
+        pdfs
+    );
+    blog!(
+        "Restore",
+        "by copying {} back into {}",
+        target.display(),
+        name
+    );
+    Ok(())
+}
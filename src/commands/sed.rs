@@ -0,0 +1,273 @@
+use crate::base::{load_papers, save_papers};
+use crate::blog;
+use crate::fields::{load_fields, save_fields};
+use crate::utils::diff::FieldChange;
+use crate::utils::io::{read_config_file, require_writable, save_config_file};
+use anyhow::{anyhow, bail, Result};
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A sed style `s/pattern/replacement/flags` substitution.
+struct Substitution {
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+/// Splits on the delimiter unless it is escaped, unescaping it.
+fn split_expression(body: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().unwrap();
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// Rewrites sed's `\1` and `&` into the regex crate's `${1}` and `${0}`.
+fn replacement(sed: &str) -> String {
+    let mut replacement = String::new();
+    let mut chars = sed.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(digit) if digit.is_ascii_digit() => {
+                    replacement.push_str(&format!("${{{}}}", digit))
+                }
+                Some('n') => replacement.push('\n'),
+                Some('$') => replacement.push_str("$$"),
+                Some(other) => replacement.push(other),
+                None => replacement.push('\\'),
+            },
+            '&' => replacement.push_str("${0}"),
+            '$' => replacement.push_str("$$"),
+            c => replacement.push(c),
+        }
+    }
+    replacement
+}
+
+impl Substitution {
+    fn parse(expression: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid expression {}, expected s/old/new/", expression);
+        let mut chars = expression.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid());
+        }
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && !c.is_whitespace() && *c != '\\')
+            .ok_or_else(invalid)?;
+        let parts = split_expression(chars.as_str(), delimiter);
+        let [pattern, sed_replacement, flags] = parts.as_slice() else {
+            return Err(invalid());
+        };
+        let mut builder = RegexBuilder::new(pattern);
+        let mut global = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                _ => bail!("Unknown flag {} in {}", flag, expression),
+            }
+        }
+        Ok(Substitution {
+            pattern: builder.build()?,
+            replacement: replacement(sed_replacement),
+            global,
+        })
+    }
+
+    /// The substituted text, if anything matched.
+    fn apply(&self, text: &str) -> Option<String> {
+        if !self.pattern.is_match(text) {
+            return None;
+        }
+        let limit = if self.global { 0 } else { 1 };
+        let replaced = self
+            .pattern
+            .replacen(text, limit, self.replacement.as_str());
+        (replaced != text).then(|| replaced.into_owned())
+    }
+}
+
+/// Renames the stacks whose names match, refusing to merge two stacks.
+fn sed_stacks(substitution: &Substitution, dry_run: bool) -> Result<usize> {
+    let mut config = read_config_file()?;
+    let renames: BTreeMap<String, String> = config
+        .stacks
+        .iter()
+        .filter_map(|stack| {
+            substitution
+                .apply(&stack.name)
+                .map(|new| (stack.name.clone(), new.trim().to_string()))
+        })
+        .collect();
+    let names: BTreeSet<String> = config
+        .stacks
+        .iter()
+        .map(|stack| renames.get(&stack.name).unwrap_or(&stack.name).clone())
+        .collect();
+    if names.len() < config.stacks.len() {
+        bail!("The substitution would give two stacks the same name")
+    }
+    if names.iter().any(|name| name.is_empty() || name == "all") {
+        bail!("The substitution would give a stack an empty or reserved name")
+    }
+
+    for (old, new) in renames.iter() {
+        blog!(if dry_run { "Would" } else { "Changed" }, "stack");
+        FieldChange {
+            field: "name".to_string(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }
+        .print();
+    }
+    if dry_run || renames.is_empty() {
+        return Ok(renames.len());
+    }
+
+    let mut papers = load_papers()?;
+    for paper in papers.values_mut() {
+        for stack in paper.stack.iter_mut() {
+            if let Some(new) = renames.get(&stack.name) {
+                stack.name = new.clone();
+            }
+        }
+    }
+    for stack in config.stacks.iter_mut() {
+        if let Some(new) = renames.get(&stack.name) {
+            stack.name = new.clone();
+        }
+    }
+    if let Some(new) = renames.get(&config.stack) {
+        config.stack = new.clone();
+    }
+    save_papers(&papers)?;
+    save_config_file(&config)?;
+    Ok(renames.len())
+}
+
+/// Applies the substitution to custom fields, all of them unless some are
+/// named, and with `stacks` to stack names. A field substituted to nothing
+/// is removed.
+pub fn sed(expression: String, fields: Vec<String>, stacks: bool, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
+    let substitution = Substitution::parse(&expression)?;
+    let fields: Vec<String> = fields.iter().map(|f| f.trim().to_lowercase()).collect();
+
+    let mut changed_fields = 0;
+    if !stacks || !fields.is_empty() {
+        let mut custom = load_fields()?;
+        for (id, values) in custom.iter_mut() {
+            let mut changes = Vec::new();
+            for (key, value) in values.iter() {
+                if !fields.is_empty() && !fields.contains(key) {
+                    continue;
+                }
+                if let Some(new) = substitution.apply(value) {
+                    let new = new.trim().to_string();
+                    changes.push(FieldChange {
+                        field: key.clone(),
+                        old: Some(value.clone()),
+                        new: (!new.is_empty()).then_some(new),
+                    });
+                }
+            }
+            if changes.is_empty() {
+                continue;
+            }
+            blog!(if dry_run { "Would" } else { "Changed" }, "{}", id);
+            for change in changes.iter() {
+                change.print();
+                match &change.new {
+                    Some(new) => values.insert(change.field.clone(), new.clone()),
+                    None => values.remove(&change.field),
+                };
+            }
+            changed_fields += changes.len();
+        }
+        custom.retain(|_, values| !values.is_empty());
+        if !dry_run && changed_fields > 0 {
+            save_fields(&custom)?;
+        }
+    }
+
+    let changed_stacks = match stacks {
+        true => sed_stacks(&substitution, dry_run)?,
+        false => 0,
+    };
+    blog!(
+        if dry_run { "Would" } else { "Done" },
+        "change {} fields and {} stacks",
+        changed_fields,
+        changed_stacks
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substitute(expression: &str, text: &str) -> Option<String> {
+        Substitution::parse(expression).unwrap().apply(text)
+    }
+
+    #[test]
+    fn accepts_any_delimiter() {
+        assert_eq!(substitute("s/a/b/", "cat"), Some("cbt".to_string()));
+        assert_eq!(substitute("s|a/b|c|", "a/b"), Some("c".to_string()));
+        assert_eq!(substitute("s#a#b#g", "aaa"), Some("bbb".to_string()));
+        assert_eq!(substitute(r"s/a\/b/c/", "a/b"), Some("c".to_string()));
+    }
+
+    #[test]
+    fn rewrites_groups_and_whole_matches() {
+        assert_eq!(
+            substitute(r"s/(\w+) (\w+)/\2 \1/", "jane doe"),
+            Some("doe jane".to_string())
+        );
+        assert_eq!(
+            substitute("s/doe/[&]/", "jane doe"),
+            Some("jane [doe]".to_string())
+        );
+        assert_eq!(substitute(r"s/doe/\&$1/", "doe"), Some("&$1".to_string()));
+    }
+
+    #[test]
+    fn honours_flags() {
+        assert_eq!(substitute("s/a/b/", "aaa"), Some("baa".to_string()));
+        assert_eq!(substitute("s/a/b/g", "aaa"), Some("bbb".to_string()));
+        assert_eq!(substitute("s/A/b/gi", "aAa"), Some("bbb".to_string()));
+        assert_eq!(substitute("s/z/b/", "aaa"), None);
+        assert_eq!(substitute("s/a/a/", "aaa"), None);
+        assert!(Substitution::parse("s/a/b/x").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in [
+            "", "y/a/b/", "s/a/b", "s/a/b/g/", "sxaxbx", r"s\a\b\", "s/(/b/",
+        ] {
+            assert!(Substitution::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
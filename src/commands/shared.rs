@@ -0,0 +1,48 @@
+use crate::base::{find_paper, load_papers, load_shared_papers, save_papers};
+use crate::embedding::{load_shared_vectors, load_vectors, save_vectors};
+use crate::utils::io::{
+    pdf_path, read_config_file, require_writable, shared_pdf_path, write_atomic,
+};
+use crate::{blog, warn};
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+
+/// Copies a paper, its pdf and its embedding from the shared library into the local one.
+pub fn copy_to_local(key: String) -> Result<()> {
+    require_writable()?;
+    let shared = load_shared_papers()?;
+    let mut paper = find_paper(&shared, &key)
+        .cloned()
+        .ok_or(anyhow!("No paper {} in the shared library", key))?;
+    let key = paper.id.clone();
+    let mut papers = load_papers()?;
+    if papers.contains_key(&key) {
+        bail!("{} is already in the library", key)
+    }
+    paper.shared = false;
+    if let Some(stack) = read_config_file()?.current_stack() {
+        paper.stack.push(stack);
+    }
+
+    let source = shared_pdf_path(&key)?;
+    if source.exists() {
+        write_atomic(&pdf_path(&key)?, &fs::read(source)?)?;
+    } else {
+        warn!("Missing", "the shared library has no pdf for {}", key);
+    }
+    match load_shared_vectors()?.remove(&key) {
+        Some(point) => {
+            let mut vectors = load_vectors()?;
+            vectors.insert(key.clone(), point);
+            save_vectors(&vectors)?;
+        }
+        None => warn!(
+            "Missing",
+            "{} has no embedding and will not show up in searches", key
+        ),
+    }
+
+    blog!("Copied", "{}", paper.title);
+    papers.shift_insert(0, key, paper);
+    save_papers(&papers)
+}
@@ -0,0 +1,164 @@
+use crate::embedding::pdf_pages;
+use crate::{blog, warn};
+use anyhow::{anyhow, Result};
+use pdf_extract::Document;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lines at the top of a page searched for an abstract heading.
+const HEAD_LINES: usize = 40;
+
+/// A paper starts on a page with an abstract heading near the top.
+fn starts_paper(page: &str) -> bool {
+    page.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(HEAD_LINES)
+        .any(|line| {
+            let lower = line.to_lowercase();
+            // A heading like "Abstract" or "Abstract." rather than prose
+            // such as "Abstract Syntax Notation" or "abstraction"
+            lower.strip_prefix("abstract").is_some_and(|rest| {
+                rest.chars()
+                    .next()
+                    .is_none_or(|c| !c.is_alphanumeric() && !c.is_whitespace())
+            })
+        })
+}
+
+/// First line long enough to be a title, above the abstract.
+fn guess_title(page: &str) -> String {
+    page.lines()
+        .map(str::trim)
+        .take_while(|line| !line.to_lowercase().starts_with("abstract"))
+        .find(|line| line.split_whitespace().count() > 3)
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Writes the pages of one part, first and last counted from one.
+fn extract(document: &Document, first: u32, last: u32, path: &Path) -> Result<()> {
+    let mut part = document.clone();
+    let outside: Vec<u32> = part
+        .get_pages()
+        .keys()
+        .copied()
+        .filter(|page| *page < first || *page > last)
+        .collect();
+    part.delete_pages(&outside);
+    part.prune_objects();
+    part.save(path)?;
+    Ok(())
+}
+
+/// Looks for several papers in one pdf, such as proceedings or merged scans,
+/// and with `write` saves each as its own pdf next to the original.
+pub fn split(path: String, write: bool) -> Result<()> {
+    let bytes = fs::read(&path)?;
+    let pages = pdf_pages(&bytes)?;
+    let mut starts: Vec<usize> = (0..pages.len())
+        .filter(|page| starts_paper(&pages[*page]))
+        .collect();
+    // A cover or title page before a single abstract is still one paper
+    if starts.len() < 2 {
+        blog!(
+            "Single",
+            "paper, no other start found in {} pages",
+            pages.len()
+        );
+        return Ok(());
+    }
+    if starts.first() != Some(&0) {
+        // Front matter before the first paper
+        starts.insert(0, 0);
+    }
+
+    let parts: Vec<(usize, usize)> = starts
+        .iter()
+        .enumerate()
+        .map(|(i, first)| {
+            (
+                *first,
+                starts.get(i + 1).map_or(pages.len(), |next| *next) - 1,
+            )
+        })
+        .collect();
+    let stem = Path::new(&path)
+        .file_stem()
+        .ok_or(anyhow!("{} is not a file", path))?
+        .to_string_lossy()
+        .to_string();
+    let document = match write {
+        true => {
+            let mut document = Document::load_mem(&bytes)?;
+            if document.is_encrypted() {
+                document
+                    .decrypt("")
+                    .map_err(|_| anyhow!("Could not split, the pdf is encrypted"))?;
+            }
+            Some(document)
+        }
+        false => None,
+    };
+
+    let mut written = Vec::new();
+    for (number, (first, last)) in parts.iter().enumerate() {
+        println!(
+            "{:>12} pp. {}-{}  {}",
+            format!("part {}", number + 1),
+            first + 1,
+            last + 1,
+            guess_title(&pages[*first])
+        );
+        if let Some(document) = &document {
+            let target: PathBuf =
+                Path::new(&path).with_file_name(format!("{}-part{}.pdf", stem, number + 1));
+            extract(document, *first as u32 + 1, *last as u32 + 1, &target)?;
+            written.push(target);
+        }
+    }
+    if written.is_empty() {
+        warn!(
+            "Suggestion",
+            "run bib split --write {} to extract them", path
+        );
+        return Ok(());
+    }
+    for target in written.iter() {
+        blog!("Saved", "{}", target.display());
+    }
+    println!("\nAdd them with:");
+    for target in written {
+        println!("  bib add -p {}", target.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_abstract_headings() {
+        assert!(starts_paper(
+            "A Title\nJane Doe\n\nAbstract\nWe study things."
+        ));
+        assert!(starts_paper("A Title\n  ABSTRACT.  \nWe study things."));
+        assert!(starts_paper("A Title\nAbstract: We study things."));
+        assert!(starts_paper("A Title\nAbstract—We study things."));
+    }
+
+    #[test]
+    fn ignores_prose_and_late_headings() {
+        assert!(!starts_paper("Abstract Syntax Notation One"));
+        assert!(!starts_paper(
+            "An abstraction of things\nabstracts are short"
+        ));
+        assert!(!starts_paper("3 Results\nThe abstract above said so."));
+        assert!(!starts_paper(""));
+        let late = format!("{}Abstract", "line\n".repeat(HEAD_LINES));
+        assert!(!starts_paper(&late));
+        let early = format!("{}Abstract", "line\n\n".repeat(HEAD_LINES - 1));
+        assert!(starts_paper(&early));
+    }
+}
@@ -2,12 +2,12 @@ use crate::base::{load_papers, save_papers, Paper};
 use crate::{
     blog,
     stacks::Stack,
-    utils::io::{read_config_file, save_config_file},
+    utils::fmt::{paint, reset, Tone},
+    utils::io::{read_config_file, require_writable, save_config_file},
 };
 use anyhow::{bail, Result};
 use indexmap::IndexMap;
 use std::collections::HashMap;
-use termion::color::{Fg, Reset, Rgb};
 
 fn count_papers_per_stack(papers: &IndexMap<String, Paper>) -> HashMap<String, usize> {
     let mut stack_counts = HashMap::new();
@@ -19,6 +19,27 @@ fn count_papers_per_stack(papers: &IndexMap<String, Paper>) -> HashMap<String, u
     stack_counts
 }
 
+fn papers_in(papers: &IndexMap<String, Paper>, name: &str) -> Vec<String> {
+    papers
+        .values()
+        .filter(|paper| paper.stack.iter().any(|s| s.name == name))
+        .map(|paper| paper.id.clone())
+        .collect()
+}
+
+/// Prints what a batch operation would change instead of writing it.
+fn print_plan(action: &str, ids: &[String], config_changed: bool) {
+    for id in ids {
+        blog!("Would", "{} {}", action, id);
+    }
+    if !ids.is_empty() {
+        blog!("Would", "write ~/.bib/papers.bin");
+    }
+    if config_changed {
+        blog!("Would", "write ~/.bib/config.toml");
+    }
+}
+
 pub fn list() -> Result<()> {
     let config = read_config_file()?;
     let current_stack = config.current_stack();
@@ -41,9 +62,9 @@ pub fn list() -> Result<()> {
             "{}{:>5} {}• {} papers{}",
             prefix,
             stack.name,
-            Fg(Rgb(83, 110, 122)),
+            paint(Tone::Muted),
             paper_count,
-            Fg(Reset),
+            reset(),
         );
     }
 
@@ -84,7 +105,10 @@ pub fn unstack() -> Result<()> {
     Ok(())
 }
 
-pub fn rename(old_name: String, new_name: String) -> Result<()> {
+pub fn rename(old_name: String, new_name: String, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
     let mut papers = load_papers()?;
     let mut config = read_config_file()?;
     // Check if new stack name is reserved
@@ -97,6 +121,12 @@ pub fn rename(old_name: String, new_name: String) -> Result<()> {
         bail!("Stack {} already exists", new_name);
     }
 
+    if dry_run {
+        let action = format!("retag {} as {} on", old_name, new_name);
+        print_plan(&action, &papers_in(&papers, &old_name), true);
+        return Ok(());
+    }
+
     // Change name of papers
     for (_, paper) in papers.iter_mut() {
         for stack in paper.stack.iter_mut() {
@@ -127,9 +157,17 @@ pub fn rename(old_name: String, new_name: String) -> Result<()> {
     blog!("Renamed", "stack {} to {}", old_name, new_name);
     Ok(())
 }
-pub fn drop(name: String) -> Result<()> {
+pub fn drop(name: String, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
     let mut papers = load_papers()?;
     let mut config = read_config_file()?;
+    if dry_run {
+        let action = format!("remove {} from", name);
+        print_plan(&action, &papers_in(&papers, &name), true);
+        return Ok(());
+    }
     config.stacks.retain(|stack| stack.name != name);
     for (_, paper) in papers.iter_mut() {
         paper.stack.retain(|stack| stack.name != name);
@@ -144,7 +182,10 @@ pub fn drop(name: String) -> Result<()> {
     Ok(())
 }
 
-pub fn merge(from: String, into: String) -> Result<()> {
+pub fn merge(from: String, into: String, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
     let mut papers = load_papers()?;
     let config = read_config_file()?;
 
@@ -156,6 +197,12 @@ pub fn merge(from: String, into: String) -> Result<()> {
         bail! {"Stack name {} does not exist",into}
     };
 
+    if dry_run {
+        let action = format!("move from {} into {}", from, into);
+        print_plan(&action, &papers_in(&papers, &from), false);
+        return Ok(());
+    }
+
     // Merge stack
     for (_, paper) in papers.iter_mut() {
         for stack in paper.stack.iter_mut() {
@@ -171,7 +218,10 @@ pub fn merge(from: String, into: String) -> Result<()> {
     Ok(())
 }
 
-pub fn fork(from: String, into: String) -> Result<()> {
+pub fn fork(from: String, into: String, dry_run: bool) -> Result<()> {
+    if !dry_run {
+        require_writable()?;
+    }
     let mut papers = load_papers()?;
     let mut config = read_config_file()?;
 
@@ -182,6 +232,12 @@ pub fn fork(from: String, into: String) -> Result<()> {
 
     let new_stack = Stack::new(&into, &config.stacks)?;
 
+    if dry_run {
+        let action = format!("add {} to", into);
+        print_plan(&action, &papers_in(&papers, &from), true);
+        return Ok(());
+    }
+
     for (_, paper) in papers.iter_mut() {
         //if it has from
         if paper.stack.iter().any(|s| s.name == from) {
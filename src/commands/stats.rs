@@ -0,0 +1,154 @@
+use crate::access::{last_access, load_accesses, Action};
+use crate::base::load_papers;
+use crate::blog;
+use crate::embedding::{centroid, load_vectors, similarities};
+use crate::ranking::top_k;
+use crate::utils::clock;
+use crate::utils::fmt::date;
+use crate::utils::io::read_config_file;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+/// Weeks of activity shown in the heatmap.
+const WEEKS: u64 = 26;
+/// Shades of the heatmap, from no activity to the busiest day.
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// One of the user's own papers counts as this many opens in their profile.
+const MINE_WEIGHT: f32 = 5.0;
+/// How many of the most relevant papers are considered forgotten candidates,
+/// as a multiple of those listed.
+const CANDIDATES: usize = 3;
+
+/// First day of the heatmap, the Monday WEEKS - 1 weeks before this week's,
+/// or the epoch itself for clocks pinned close to it.
+fn first_day(today: u64) -> u64 {
+    // Days since the epoch start on a Thursday, Monday is 0
+    today.saturating_sub((today + 3) % 7 + (WEEKS - 1) * 7)
+}
+
+/// Size of the library and a heatmap of when papers were opened or yanked.
+pub fn stats() -> Result<()> {
+    let config = read_config_file()?;
+    let papers = load_papers()?;
+    let accesses = load_accesses()?;
+    let pdfs = papers
+        .values()
+        .filter(|paper| paper.pdf_location().is_ok_and(|path| path.exists()))
+        .count();
+    blog!(
+        "Library",
+        "{} papers, {} pdfs, {} stacks",
+        papers.len(),
+        pdfs,
+        config.stacks.len()
+    );
+
+    let today = clock::now()? / 86400;
+    let first = first_day(today);
+    let mut per_day: BTreeMap<u64, usize> = BTreeMap::new();
+    let (mut opens, mut yanks) = (0, 0);
+    for access in accesses.values().flatten() {
+        let day = access.at / 86400;
+        if day < first || day > today {
+            continue;
+        }
+        *per_day.entry(day).or_default() += 1;
+        match access.action {
+            Action::Open => opens += 1,
+            Action::Yank => yanks += 1,
+        }
+    }
+    blog!(
+        "Activity",
+        "{} opens and {} yanks in the last {} weeks",
+        opens,
+        yanks,
+        WEEKS
+    );
+    let busiest = per_day.values().copied().max().unwrap_or(0);
+    println!();
+    for (weekday, name) in WEEKDAYS.iter().enumerate() {
+        let row: String = (0..WEEKS)
+            .map(|week| first + week * 7 + weekday as u64)
+            .map(|day| match per_day.get(&day) {
+                _ if day > today => ' ',
+                None => SHADES[0],
+                // Any activity shows, the busiest day gets the darkest shade
+                Some(count) => SHADES[1 + (count * (SHADES.len() - 1) - 1) / busiest],
+            })
+            .collect();
+        println!("{:>12} {}", name, row);
+    }
+    println!(
+        "{:>12} {} to {}",
+        "",
+        date(first * 86400),
+        date(today * 86400)
+    );
+    Ok(())
+}
+
+/// Papers close to what the user writes and reads that have gone unopened
+/// the longest, never opened ones first.
+pub fn forgotten(max: usize) -> Result<()> {
+    let config = read_config_file()?;
+    config
+        .features
+        .require_embeddings("Finding forgotten papers")?;
+    let papers = load_papers()?;
+    let vectors = load_vectors()?;
+    let accesses = load_accesses()?;
+
+    let mut weights: BTreeMap<String, f32> = config
+        .mine
+        .iter()
+        .map(|id| (id.clone(), MINE_WEIGHT))
+        .collect();
+    for (id, log) in accesses.iter() {
+        *weights.entry(id.clone()).or_default() += log.len() as f32;
+    }
+    let profile = match centroid(&vectors, &weights) {
+        Some(profile) => profile,
+        None => bail!("No profile yet, mark your papers with bib mine or open a few"),
+    };
+
+    let ids: Vec<String> = papers
+        .keys()
+        .filter(|id| !config.mine.contains(id))
+        .cloned()
+        .collect();
+    let mut relevant = top_k(similarities(&profile, &vectors, &ids), max * CANDIDATES);
+    relevant.sort_by_key(|(id, _)| last_access(&accesses, id).unwrap_or(0));
+    let (width, _) = termion::terminal_size()?;
+    for (id, _) in relevant.into_iter().take(max) {
+        let when = match last_access(&accesses, id) {
+            Some(at) => date(at),
+            None => "never opened".to_string(),
+        };
+        println!(
+            "{:>12} {}",
+            when,
+            papers[id].display(width.saturating_sub(13))
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heatmap_starts_on_a_monday() {
+        // 2024-06-13 was a Thursday, 2023-12-18 a Monday 25 weeks earlier
+        assert_eq!(first_day(19887), 19709);
+        assert_eq!(date(19709 * 86400), "2023-12-18");
+    }
+
+    #[test]
+    fn heatmap_stops_at_the_epoch() {
+        assert_eq!(first_day(0), 0);
+        assert_eq!(first_day(100), 0);
+    }
+}
@@ -1,20 +1,62 @@
+use crate::ranking::top_k;
+use crate::utils::clock;
 use crate::utils::io::model_dir;
-use crate::{blog, utils};
-use anyhow::{bail, Result};
+use crate::{blog, utils, warn};
+use anyhow::{anyhow, bail, Context, Result};
 use bincode::{deserialize, serialize};
 use dotzilla;
 use fastembed::{
-    read_file_to_bytes, InitOptionsUserDefined, Pooling, QuantizationMode, TextEmbedding,
-    TokenizerFiles, UserDefinedEmbeddingModel,
+    read_file_to_bytes, EmbeddingModel, InitOptions, InitOptionsUserDefined, Pooling,
+    QuantizationMode, TextEmbedding, TokenizerFiles, UserDefinedEmbeddingModel,
 };
 use gag::Gag;
 use hf_hub::api::sync::ApiBuilder;
 use hf_hub::Cache;
+use pdf_extract::{
+    output_doc, ConvertToFmt, Document, MediaBox, OutputDev, OutputError, PlainTextOutput,
+    Transform,
+};
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::collections::{BTreeMap, BinaryHeap};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
+use std::rc::Rc;
+
+/// Embedding models to choose from with `features.model` in the config.
+/// Vectors of one model cannot be compared with another's, so switching
+/// needs a bib reembed.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Model {
+    /// jina-embeddings-v2-small-en, which reads long English texts
+    #[default]
+    English,
+    /// paraphrase-multilingual-MiniLM-L12-v2, which matches queries in some
+    /// fifty languages with papers in any of them, but reads less of each
+    Multilingual,
+}
+
+impl Model {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::English => "jinaai/jina-embeddings-v2-small-en",
+            Model::Multilingual => "Xenova/paraphrase-multilingual-MiniLM-L12-v2",
+        }
+    }
+}
+
+/// Cached query embeddings older than this are computed again.
+const QUERY_TTL: u64 = 30 * 24 * 60 * 60;
+/// Most recent queries kept in the cache.
+const MAX_QUERIES: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
+struct CachedQuery {
+    coords: Vec<f32>,
+    created: u64,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Point {
@@ -23,20 +65,165 @@ pub struct Point {
 }
 
 impl Point {
+    pub fn dim(&self) -> usize {
+        self.coords.len()
+    }
+    pub fn with_id(self, id: String) -> Self {
+        Point { id, ..self }
+    }
     pub fn from_bytes(id: String, bytes: Vec<u8>) -> Result<Self> {
+        if synthetic()? {
+            let coords = synthetic_embedding(&pdf_text(bytes)?);
+            return Ok(Point { id, coords });
+        }
+        Self::from_bytes_with(&text_model()?, id, bytes)
+    }
+    /// Embeds a text without the model, see `synthetic_embedding`.
+    pub fn synthetic(id: String, text: &str) -> Self {
+        let coords = synthetic_embedding(text);
+        Point { id, coords }
+    }
+    /// Embeds with an already loaded model, for callers that embed many pdfs.
+    pub fn from_bytes_with(model: &TextEmbedding, id: String, bytes: Vec<u8>) -> Result<Self> {
         //let text = extract_ascii_only(bytes)?;
         blog!("Extracting", "text from pdf");
-        let text: String;
-        {
-            let _print_gag = Gag::stdout().unwrap();
-            text = extract_text_from_pdf(bytes)?;
-        }
-        blog!("Embedding", "using JINA-v2-small-8k");
-        let coords = encode(&text)?;
+        let text = pdf_text(bytes)?;
+        check_text_quality(&text);
+        blog!("Embedding", "using {}", model_name()?);
+        let coords = encode_with(model, &text)?;
         Ok(Point { id, coords })
     }
 }
 
+/// Extracts the text of a pdf, silencing what pdf-extract prints along the way.
+pub fn pdf_text(bytes: Vec<u8>) -> Result<String> {
+    let _print_gag = Gag::stdout().unwrap();
+    extract_text_from_pdf(bytes)
+        .context("Could not extract text, the pdf may be encrypted or a scan")
+}
+
+/// Below this many words the pdf is most likely a scanned image.
+const MIN_WORDS: usize = 250;
+/// Broken font encodings extract as symbol soup rather than words.
+const MIN_LETTER_RATIO: f32 = 0.6;
+
+/// Warns when the extracted text is too poor for the embedding to be found by any search.
+fn check_text_quality(text: &str) {
+    let words = text.split_whitespace().count();
+    let (letters, visible) = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .fold((0, 0), |(letters, visible), c| {
+            (letters + c.is_alphabetic() as usize, visible + 1)
+        });
+    let ratio = letters as f32 / visible.max(1) as f32;
+    if words < MIN_WORDS {
+        warn!(
+            "Low text",
+            "only {} words could be extracted, the pdf is probably a scan", words
+        );
+    } else if ratio < MIN_LETTER_RATIO {
+        warn!(
+            "Low text",
+            "extracted text is only {:.0}% letters, the pdf fonts are probably broken",
+            ratio * 100.0
+        );
+    } else {
+        return;
+    }
+    warn!(
+        "Suggestion",
+        "run it through OCR (e.g. ocrmypdf) and add it again"
+    );
+}
+
+/// Collects the text of every page separately, so hits can be located in the pdf.
+struct PageText {
+    pages: Rc<RefCell<Vec<String>>>,
+    plain: PlainTextOutput<PageSink>,
+}
+
+/// Appends to the last page being extracted.
+struct PageSink(Rc<RefCell<Vec<String>>>);
+
+impl fmt::Write for PageSink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(page) = self.0.borrow_mut().last_mut() {
+            page.push_str(s);
+        }
+        Ok(())
+    }
+}
+
+impl ConvertToFmt for PageSink {
+    type Writer = PageSink;
+    fn convert(self) -> Self::Writer {
+        self
+    }
+}
+
+impl OutputDev for PageText {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.pages.borrow_mut().push(String::new());
+        self.plain.begin_page(page_num, media_box, art_box)
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.plain.end_page()
+    }
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        self.plain
+            .output_character(trm, width, spacing, font_size, char)
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.plain.begin_word()
+    }
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.plain.end_word()
+    }
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.plain.end_line()
+    }
+}
+
+/// Text of each page of a pdf, first page first.
+pub fn pdf_pages(bytes: &[u8]) -> Result<Vec<String>> {
+    let _print_gag = Gag::stdout().unwrap();
+    let pages = Rc::new(RefCell::new(Vec::new()));
+    let mut output = PageText {
+        pages: pages.clone(),
+        plain: PlainTextOutput::new(PageSink(pages.clone())),
+    };
+    let mut doc = Document::load_mem(bytes)
+        .context("Could not extract text, the pdf may be encrypted or a scan")?;
+    if doc.is_encrypted() {
+        doc.decrypt("")
+            .map_err(|_| anyhow!("Could not extract text, the pdf is encrypted"))?;
+    }
+    output_doc(&doc, &mut output)?;
+    drop(output);
+    let pages = pages.take();
+    Ok(pages
+        .into_iter()
+        .map(|page| {
+            page.chars()
+                .filter(|&c| (c.is_ascii() && !c.is_control()) || c == '\n')
+                .collect()
+        })
+        .collect())
+}
+
 fn extract_text_from_pdf(bytes: Vec<u8>) -> Result<String> {
     let text = pdf_extract::extract_text_from_mem(&bytes)?;
     Ok(text
@@ -54,7 +241,7 @@ fn load_model() -> Result<UserDefinedEmbeddingModel> {
         .build()
         .unwrap();
 
-    let repo = api.model("jinaai/jina-embeddings-v2-small-en".to_string());
+    let repo = api.model(Model::English.name().to_string());
 
     let tokenizer_files: TokenizerFiles = TokenizerFiles {
         tokenizer_file: read_file_to_bytes(&repo.get("tokenizer.json")?)?,
@@ -75,9 +262,7 @@ fn load_model() -> Result<UserDefinedEmbeddingModel> {
 pub fn save_vectors(vectors: &BTreeMap<String, Point>) -> Result<()> {
     let encoded: Vec<u8> = serialize(vectors)?;
     let filename = utils::io::vectors_path()?;
-    let mut file = File::create(filename)?;
-    file.write_all(&encoded)?;
-    Ok(())
+    utils::io::write_atomic(&filename, &encoded)
 }
 
 pub fn load_vectors() -> Result<BTreeMap<String, Point>> {
@@ -92,10 +277,102 @@ pub fn load_vectors() -> Result<BTreeMap<String, Point>> {
     Ok(decoded)
 }
 
+/// Vectors of the shared library, which is only ever read.
+pub fn load_shared_vectors() -> Result<BTreeMap<String, Point>> {
+    let filename = match utils::io::shared_path("vectors.bin")? {
+        Some(filename) if filename.exists() => filename,
+        _ => return Ok(BTreeMap::new()),
+    };
+    let buffer = std::fs::read(filename)?;
+    let decoded: BTreeMap<String, Point> = deserialize(&buffer)?;
+    Ok(decoded)
+}
+
+/// Same size as the English model's embeddings, so reembed treats both alike.
+const SYNTHETIC_DIM: usize = 512;
+
+fn synthetic() -> Result<bool> {
+    Ok(utils::io::read_config_file()?.features.synthetic)
+}
+
+/// Stand-in for the model that hashes every word to a signed coordinate.
+/// Texts sharing words point the same way, which is enough to try searches.
+pub fn synthetic_embedding(text: &str) -> Vec<f32> {
+    let mut coords = vec![0.0; SYNTHETIC_DIM];
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+    {
+        // FNV-1a, stable across runs and platforms unlike the std hasher
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        coords[(hash % SYNTHETIC_DIM as u64) as usize] += sign;
+    }
+    let norm = coords.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        coords.iter_mut().for_each(|x| *x /= norm);
+    }
+    coords
+}
+
+pub fn text_model() -> Result<TextEmbedding> {
+    let features = utils::io::read_config_file()?.features;
+    if features.synthetic {
+        bail!("Synthetic embeddings are on, set features.synthetic = false in config.toml to use the model")
+    }
+    match features.model {
+        Model::English => {
+            let jina_model = load_model()?;
+            let jina_options = InitOptionsUserDefined::new().with_max_length(8192); // <- Jina FTW
+            TextEmbedding::try_new_from_user_defined(jina_model, jina_options)
+        }
+        Model::Multilingual => TextEmbedding::try_new(
+            InitOptions::new(EmbeddingModel::ParaphraseMLMiniLML12V2)
+                .with_cache_dir(model_dir()?)
+                .with_show_download_progress(true),
+        ),
+    }
+}
+
+/// What embeds texts, synthetic or the configured model, to tell vectors of
+/// different models apart.
+pub fn model_name() -> Result<&'static str> {
+    let features = utils::io::read_config_file()?.features;
+    Ok(match features.synthetic {
+        true => "synthetic",
+        false => features.model.name(),
+    })
+}
+
+/// Records the model every vector in vectors.bin was embedded with.
+pub fn save_vectors_model(name: &str) -> Result<()> {
+    let filename = utils::io::vectors_model_path()?;
+    utils::io::write_atomic(&filename, name.as_bytes())
+}
+
+/// The model of the last full reembed, unknown for older libraries.
+pub fn load_vectors_model() -> Result<Option<String>> {
+    let filename = utils::io::vectors_model_path()?;
+    if !filename.exists() {
+        return Ok(None);
+    }
+    Ok(Some(std::fs::read_to_string(filename)?.trim().to_string()))
+}
+
 pub fn encode(sentence: &str) -> Result<Vec<f32>> {
-    let jina_model = load_model()?;
-    let jina_options = InitOptionsUserDefined::new().with_max_length(8192); // <- Jina FTW
-    let model = TextEmbedding::try_new_from_user_defined(jina_model, jina_options)?;
+    if synthetic()? {
+        return Ok(synthetic_embedding(sentence));
+    }
+    encode_with(&text_model()?, sentence)
+}
+
+/// Encodes with an already loaded model, for callers that embed many texts.
+pub fn encode_with(model: &TextEmbedding, sentence: &str) -> Result<Vec<f32>> {
     let documents = vec![sentence];
     let embeddings = model.embed(documents, None)?;
     // Check if there is at least one embedding
@@ -106,54 +383,156 @@ pub fn encode(sentence: &str) -> Result<Vec<f32>> {
     Ok(embeddings.into_iter().next().unwrap())
 }
 
-#[derive(Clone, Debug)]
-struct PointDistance {
-    id: String,
-    dist: f32,
+/// Encodes a batch of texts in one pass of the model.
+pub fn encode_many_with(model: &TextEmbedding, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let embeddings = model.embed(texts.to_vec(), None)?;
+    if embeddings.len() != texts.len() {
+        bail!("No embeddings were generated.");
+    }
+    Ok(embeddings)
 }
 
-impl Eq for PointDistance {}
+/// Embeds a search query, reusing the embedding of a recent identical query.
+pub fn encode_query(query: &str) -> Result<Vec<f32>> {
+    cached_encode(query, encode)
+}
 
-impl PartialEq for PointDistance {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+/// Looks the query up in the cache before falling back to `encode`.
+pub fn cached_encode<F>(query: &str, encode: F) -> Result<Vec<f32>>
+where
+    F: FnOnce(&str) -> Result<Vec<f32>>,
+{
+    let normalized = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    let key = format!("{}|{}", model_name()?, normalized.to_lowercase());
+    let now = clock::now()?;
+    let mut cache = load_queries()?;
+    if let Some(cached) = cache.get(&key) {
+        if now.saturating_sub(cached.created) < QUERY_TTL {
+            return Ok(cached.coords.clone());
+        }
     }
+    let coords = encode(&normalized)?;
+    cache.insert(
+        key,
+        CachedQuery {
+            coords: coords.clone(),
+            created: now,
+        },
+    );
+    cache.retain(|_, cached| now.saturating_sub(cached.created) < QUERY_TTL);
+    if cache.len() > MAX_QUERIES {
+        let mut created: Vec<u64> = cache.values().map(|cached| cached.created).collect();
+        created.sort_unstable_by(|a, b| b.cmp(a));
+        let oldest = created[MAX_QUERIES - 1];
+        cache.retain(|_, cached| cached.created >= oldest);
+    }
+    save_queries(&cache)?;
+    Ok(coords)
+}
+
+fn save_queries(queries: &BTreeMap<String, CachedQuery>) -> Result<()> {
+    let encoded: Vec<u8> = serialize(queries)?;
+    let filename = utils::io::queries_path()?;
+    utils::io::write_cache(&filename, &encoded)
 }
 
-impl Ord for PointDistance {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.dist.partial_cmp(&self.dist).unwrap()
+fn load_queries() -> Result<BTreeMap<String, CachedQuery>> {
+    let filename = utils::io::queries_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
     }
+    let mut file = File::open(filename)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    // A cache that no longer decodes is simply started over
+    Ok(deserialize(&buffer).unwrap_or_default())
+}
+
+/// Embeddings of the pages of a paper, computed the first time it is searched.
+#[derive(Serialize, Deserialize)]
+pub struct PageVectors {
+    pub pages: Vec<String>,
+    pub coords: Vec<Vec<f32>>,
+}
+
+pub fn save_pages(pages: &BTreeMap<String, PageVectors>) -> Result<()> {
+    let encoded: Vec<u8> = serialize(pages)?;
+    let filename = utils::io::pages_path()?;
+    utils::io::write_cache(&filename, &encoded)
 }
 
-impl PartialOrd for PointDistance {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+pub fn load_pages() -> Result<BTreeMap<String, PageVectors>> {
+    let filename = utils::io::pages_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
     }
+    let buffer = std::fs::read(filename)?;
+    // Pages can always be embedded again, so a broken cache is started over
+    Ok(deserialize(&buffer).unwrap_or_default())
+}
+
+/// Similarity of the query to each of the papers that have a vector, scored
+/// lazily so callers can stream them into `top_k`.
+pub fn similarities<'a>(
+    query: &'a [f32],
+    points: &'a BTreeMap<String, Point>,
+    ids: &'a [String],
+) -> impl Iterator<Item = (&'a String, f32)> + 'a {
+    // Vectors from another model cannot be compared with the query
+    let comparable = move |point: &Point| point.coords.len() == query.len();
+    let mismatched = ids
+        .iter()
+        .filter_map(|id| points.get(id))
+        .filter(|point| !comparable(point))
+        .count();
+    if mismatched > 0 {
+        warn!(
+            "Skipped",
+            "{} papers embedded with a different model, run bib reembed", mismatched
+        );
+    }
+    ids.iter().filter_map(move |id| {
+        let point = points.get(id).filter(|point| comparable(point))?;
+        Some((id, dotzilla::dot(query, &point.coords)))
+    })
+}
+
+/// Weighted mean direction of the given papers' vectors, normalized. Vectors
+/// of another size than the first are left out.
+pub fn centroid(
+    points: &BTreeMap<String, Point>,
+    weights: &BTreeMap<String, f32>,
+) -> Option<Vec<f32>> {
+    let mut sum: Option<Vec<f32>> = None;
+    for (id, weight) in weights {
+        let point = match points.get(id) {
+            Some(point) => point,
+            None => continue,
+        };
+        let sum = sum.get_or_insert_with(|| vec![0.0; point.coords.len()]);
+        if sum.len() == point.coords.len() {
+            sum.iter_mut()
+                .zip(&point.coords)
+                .for_each(|(total, x)| *total += weight * x);
+        }
+    }
+    let mut sum = sum?;
+    let norm = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return None;
+    }
+    sum.iter_mut().for_each(|x| *x /= norm);
+    Some(sum)
 }
 
 pub fn k_nearest(
     query: &[f32],
     points: &BTreeMap<String, Point>,
-    ids: &Vec<String>,
+    ids: &[String],
     k: usize,
 ) -> Vec<String> {
-    let mut heap = BinaryHeap::with_capacity(k + 1);
-    for id in ids {
-        let dist = dotzilla::dot(query, &points[id].coords);
-        let point_dist = PointDistance {
-            id: id.to_string(),
-            dist,
-        };
-        if heap.len() < k {
-            heap.push(point_dist);
-        } else if dist > heap.peek().unwrap().dist {
-            heap.pop();
-            heap.push(point_dist);
-        }
-    }
-    heap.into_sorted_vec()
-        .iter()
-        .map(|pd| pd.id.clone())
+    top_k(similarities(query, points, ids), k)
+        .into_iter()
+        .map(|(id, _)| id.clone())
         .collect()
 }
@@ -0,0 +1,68 @@
+use crate::utils;
+use anyhow::{bail, Result};
+use bincode::{deserialize, serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+pub type Fields = BTreeMap<String, String>;
+
+/// Parses a `key=value` assignment. An empty value means the field is removed.
+pub fn parse_assignment(input: &str) -> Result<(String, String)> {
+    let (key, value) = match input.split_once('=') {
+        Some(pair) => pair,
+        None => bail!("Invalid field {}, expected key=value", input),
+    };
+    let key = key.trim().to_lowercase();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!("Invalid field name {}", key)
+    }
+    Ok((key, value.trim().to_string()))
+}
+
+/// Checks that all `key=value` filters hold for the given fields.
+pub fn matches(fields: Option<&Fields>, filters: &[(String, String)]) -> bool {
+    filters.iter().all(|(key, value)| {
+        fields
+            .and_then(|fields| fields.get(key))
+            .is_some_and(|v| v.eq_ignore_ascii_case(value))
+    })
+}
+
+/// Appends the custom fields to the bibtex entry.
+pub fn with_fields(bibtex: &str, fields: Option<&Fields>) -> String {
+    let fields = match fields {
+        Some(fields) if !fields.is_empty() => fields,
+        _ => return bibtex.to_string(),
+    };
+    let body = bibtex.trim_end();
+    let body = body.strip_suffix('}').unwrap_or(body).trim_end();
+    let mut entry = body.strip_suffix(',').unwrap_or(body).to_string();
+    for (key, value) in fields {
+        entry.push_str(&format!(",\n    {} = {{{}}}", key, value));
+    }
+    entry.push_str("\n}");
+    entry
+}
+
+pub fn save_fields(fields: &BTreeMap<String, Fields>) -> Result<()> {
+    let encoded: Vec<u8> = serialize(fields)?;
+    let filename = utils::io::fields_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_fields() -> Result<BTreeMap<String, Fields>> {
+    let filename = utils::io::fields_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let mut file = File::open(filename)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let decoded: BTreeMap<String, Fields> = deserialize(&buffer)?;
+    Ok(decoded)
+}
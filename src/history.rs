@@ -0,0 +1,60 @@
+use crate::utils;
+use crate::utils::clock;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Queries remembered for each paper.
+const MAX_MATCHES: usize = 20;
+
+/// A search that brought up the paper.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Match {
+    pub query: String,
+    pub score: f32,
+    pub at: u64,
+}
+
+/// Searches that found each paper, by paper id, latest last.
+pub type History = BTreeMap<String, Vec<Match>>;
+
+/// Remembers that the query found these papers, replacing an earlier
+/// record of the same query.
+pub fn record(query: &str, found: &[(String, f32)]) -> Result<()> {
+    if utils::io::read_only() {
+        return Ok(());
+    }
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    let at = clock::now()?;
+    let mut history = load_history()?;
+    for (id, score) in found {
+        let matches = history.entry(id.clone()).or_default();
+        matches.retain(|m| !m.query.eq_ignore_ascii_case(&query));
+        matches.push(Match {
+            query: query.clone(),
+            score: *score,
+            at,
+        });
+        if matches.len() > MAX_MATCHES {
+            matches.remove(0);
+        }
+    }
+    save_history(&history)
+}
+
+pub fn save_history(history: &History) -> Result<()> {
+    let encoded: Vec<u8> = serialize(history)?;
+    let filename = utils::io::history_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_history() -> Result<History> {
+    let filename = utils::io::history_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: History = deserialize(&buffer)?;
+    Ok(decoded)
+}
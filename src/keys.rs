@@ -0,0 +1,195 @@
+use crate::base::Paper;
+use crate::parser::arxiv::STOP_WORD;
+use crate::utils::collate::collation_key;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Citation key conventions, shown for "Attention Is All You Need" (2017).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyStyle {
+    /// Vaswani2017
+    AuthorYear,
+    /// Vaswani2017Attention
+    AuthorYearWord,
+    /// VaswaniAttentionAllYou
+    AuthorTitle,
+    /// vaswani2017attention, as Google Scholar and arXiv imports do
+    Scholar,
+}
+
+/// Title words a short title is made of.
+const SHORT_TITLE_WORDS: usize = 3;
+
+impl KeyStyle {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "author-year" => Ok(KeyStyle::AuthorYear),
+            "author-year-word" => Ok(KeyStyle::AuthorYearWord),
+            "author-title" => Ok(KeyStyle::AuthorTitle),
+            "scholar" => Ok(KeyStyle::Scholar),
+            _ => bail!("Unknown key style {}", name),
+        }
+    }
+
+    /// Key of the paper in this style, before any collision suffix.
+    pub fn key(&self, paper: &Paper) -> String {
+        let family = paper
+            .family_name()
+            .or_else(|| paper.author.split_whitespace().next().map(String::from))
+            .unwrap_or_else(|| "anon".to_string());
+        let family = words(&family).concat();
+        let title: Vec<String> = words(&paper.title)
+            .into_iter()
+            .filter(|word| !STOP_WORD.contains(&word.as_str()))
+            .collect();
+        let first = title.first().cloned().unwrap_or_default();
+        match self {
+            KeyStyle::AuthorYear => format!("{}{}", capitalize(&family), paper.year),
+            KeyStyle::AuthorYearWord => {
+                format!(
+                    "{}{}{}",
+                    capitalize(&family),
+                    paper.year,
+                    capitalize(&first)
+                )
+            }
+            KeyStyle::AuthorTitle => {
+                let short: String = title
+                    .iter()
+                    .take(SHORT_TITLE_WORDS)
+                    .map(|word| capitalize(word))
+                    .collect();
+                format!("{}{}", capitalize(&family), short)
+            }
+            KeyStyle::Scholar => format!("{}{}{}", family, paper.year, first),
+        }
+    }
+}
+
+/// Lowercase ascii words of the text, accents folded away.
+fn words(text: &str) -> Vec<String> {
+    collation_key(text)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The key itself if free, otherwise with the first free suffix a to z,
+/// then numbers.
+pub fn unique_key(key: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(key) {
+        return key.to_string();
+    }
+    ('a'..='z')
+        .map(|suffix| format!("{}{}", key, suffix))
+        .chain((2..).map(|suffix| format!("{}{}", key, suffix)))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+/// Whether the key is the base key, alone or with a suffix `unique_key` gives.
+pub fn matches_key(key: &str, base: &str) -> bool {
+    match key.strip_prefix(base) {
+        Some("") => true,
+        Some(suffix) if suffix.len() == 1 => suffix.chars().all(|c| c.is_ascii_lowercase()),
+        Some(suffix) => !suffix.starts_with('0') && suffix.parse::<u32>().is_ok_and(|n| n >= 2),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attention() -> Paper {
+        Paper::from_bibtex(
+            "@inproceedings{vaswani, title = {Attention Is All You Need}, \
+             author = {Vaswani, Ashish and Shazeer, Noam}, year = {2017}}",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn formats_each_style() {
+        let paper = attention();
+        assert_eq!(KeyStyle::AuthorYear.key(&paper), "Vaswani2017");
+        assert_eq!(KeyStyle::AuthorYearWord.key(&paper), "Vaswani2017Attention");
+        assert_eq!(KeyStyle::AuthorTitle.key(&paper), "VaswaniAttentionAllYou");
+        assert_eq!(KeyStyle::Scholar.key(&paper), "vaswani2017attention");
+    }
+
+    #[test]
+    fn folds_accents_and_reads_family_names() {
+        let paper = Paper::from_bibtex(
+            "@article{x, title = {Über formal unentscheidbare Sätze}, \
+             author = {Kurt Gödel}, year = {1931}}",
+        )
+        .unwrap();
+        assert_eq!(KeyStyle::Scholar.key(&paper), "godel1931uber");
+    }
+
+    #[test]
+    fn keys_volumes_and_reports_without_authors() {
+        let proceedings = Paper::from_bibtex(
+            "@proceedings{x, title = {Advances in Neural Information Processing Systems}, \
+             editor = {Guyon, Isabelle and von Luxburg, Ulrike}, year = {2017}}",
+        )
+        .unwrap();
+        assert_eq!(proceedings.author, "Guyon and Luxburg (ed.)");
+        assert_eq!(KeyStyle::AuthorYear.key(&proceedings), "Guyon2017");
+        let report = Paper::from_bibtex(
+            "@techreport{y, title = {The Fortran Automatic Coding System}, \
+             institution = {IBM}, year = {1957}}",
+        )
+        .unwrap();
+        assert_eq!(report.author, "IBM");
+        assert_eq!(KeyStyle::Scholar.key(&report), "ibm1957fortran");
+    }
+
+    #[test]
+    fn parses_style_names() {
+        assert_eq!(
+            KeyStyle::parse("author-year").unwrap(),
+            KeyStyle::AuthorYear
+        );
+        assert_eq!(KeyStyle::parse("scholar").unwrap(), KeyStyle::Scholar);
+        assert!(KeyStyle::parse("AuthorYear").is_err());
+    }
+
+    #[test]
+    fn unique_key_suffixes_letters_then_numbers() {
+        assert_eq!(unique_key("Doe2020", |_| false), "Doe2020");
+        assert_eq!(unique_key("Doe2020", |key| key == "Doe2020"), "Doe2020a");
+        assert_eq!(
+            unique_key("Doe2020", |key| ["Doe2020", "Doe2020a", "Doe2020b"]
+                .contains(&key)),
+            "Doe2020c"
+        );
+        let letters = |key: &str| {
+            key.strip_prefix("Doe2020")
+                .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_lowercase()))
+        };
+        assert_eq!(unique_key("Doe2020", letters), "Doe20202");
+    }
+
+    #[test]
+    fn matches_suffixed_keys() {
+        assert!(matches_key("Doe2020", "Doe2020"));
+        assert!(matches_key("Doe2020b", "Doe2020"));
+        assert!(matches_key("Doe202012", "Doe2020"));
+        assert!(!matches_key("Doe2020B", "Doe2020"));
+        assert!(!matches_key("Doe20201", "Doe2020"));
+        assert!(!matches_key("Doe2020ab", "Doe2020"));
+        assert!(!matches_key("Doe2021", "Doe2020"));
+    }
+}
@@ -1,10 +1,22 @@
 use clap::{Parser, Subcommand};
-use termion::color;
+use std::env;
+use std::process::ExitCode;
+use utils::fmt::{marker, paint, reset, Tone};
+mod access;
+mod annotations;
 mod base;
+mod claims;
 mod commands;
 mod embedding;
+mod fields;
+mod history;
+mod keys;
 mod parser;
+mod quotes;
+mod ranking;
+mod revisions;
 mod stacks;
+mod tombstones;
 mod utils;
 
 #[derive(Parser)]
@@ -13,6 +25,10 @@ mod utils;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Disable colored output, also done by setting NO_COLOR
+    #[clap(long, action, global = true)]
+    no_color: bool,
 }
 
 #[derive(Subcommand)]
@@ -28,6 +44,33 @@ enum Commands {
         /// Flag to indicate if it's a PDF
         #[clap(long, short, action, group = "from")]
         web: bool,
+        /// Leave the pdf where it is instead of moving it into ~/.bib/pdfs
+        #[clap(long, action, requires = "pdf")]
+        link: bool,
+        /// Read arxiv links or dois from stdin, one per line
+        #[clap(long, action, group = "from")]
+        stdin: bool,
+        /// Print what would be added without fetching anything
+        #[clap(long, action, requires = "stdin")]
+        dry_run: bool,
+        /// Skip papers that were removed with bib remove instead of warning
+        #[clap(long, action)]
+        respect_tombstones: bool,
+    },
+    /// Add every pdf in a folder tree, found by the doi or arxiv id it prints
+    Import {
+        /// Folder to look for pdfs in, subfolders included
+        #[clap(long, value_name = "DIR")]
+        dir: String,
+        /// Leave the pdfs where they are instead of copying them into ~/.bib/pdfs
+        #[clap(long, action)]
+        link: bool,
+        /// Print the pdfs that would be added without reading them
+        #[clap(long, action)]
+        dry_run: bool,
+        /// Skip papers that were removed with bib remove instead of warning
+        #[clap(long, action)]
+        respect_tombstones: bool,
     },
     /// Open pdf manually
     Open {
@@ -35,19 +78,303 @@ enum Commands {
         #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
         query: String,
     },
+    /// Open the best match for a title, key or author right away
+    O {
+        #[clap(value_name = "QUERY")]
+        query: String,
+    },
+    /// Delete a reference and its pdf, remembering it was removed
+    Remove {
+        /// Initial query for searching
+        #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
+        query: String,
+        /// Do not ask for confirmation
+        #[clap(long, short, action)]
+        yes: bool,
+        /// Print the stores and files that would change without removing anything
+        #[clap(long, action)]
+        dry_run: bool,
+    },
     /// Copy bibtex to clipboard
     Yank {
         /// Initial query for searching
         #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
         query: String,
     },
+    /// Show the bibtex and custom fields of a reference
+    Show {
+        /// Initial query for searching
+        #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
+        query: String,
+    },
+    /// Set custom fields of a reference
+    Edit {
+        /// Initial query for searching
+        #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
+        query: String,
+        /// Custom field as key=value, leave value empty to remove it
+        #[clap(long = "field", short, value_name = "KEY=VALUE")]
+        fields: Vec<String>,
+    },
+    /// Mark or unmark a reference as authored by you
+    Mine {
+        /// Initial query for searching
+        #[clap(value_name = "PROMPT", default_value_t = String::from(""))]
+        query: String,
+    },
+    /// Publication list of the references marked as yours
+    Cv {
+        /// Only include papers from this year on
+        #[clap(long, value_name = "YEAR")]
+        since: Option<i64>,
+        /// Output format
+        #[clap(long, default_value = "md", value_parser = ["md", "bib"])]
+        format: String,
+    },
+    /// Handout of the papers in a stack, for journal clubs and seminars
+    ReadingList {
+        /// Stack to list, the current one by default
+        #[clap(long, value_name = "NAME")]
+        stack: Option<String>,
+        /// Output format, pdf needs pandoc
+        #[clap(long, default_value = "md", value_parser = ["md", "pdf"])]
+        format: String,
+        /// File to write, markdown goes to stdout otherwise
+        #[clap(long, short, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Cite while you write: turn copied titles into citations
+    Cwyw {
+        /// Copy [@key] instead of \cite{key}
+        #[clap(long, action)]
+        pandoc: bool,
+    },
+    /// Search the library, or with --in where a paper discusses something
+    Find {
+        /// Question or topic to look for
+        #[clap(value_name = "QUESTION")]
+        query: String,
+        /// Search the pages of this paper instead of the library
+        #[clap(long = "in", value_name = "KEY OR DOI")]
+        key: Option<String>,
+        /// Number of papers or pages to return
+        #[clap(value_name = "LENGTH", short, long)]
+        max: Option<usize>,
+    },
+    /// Verbatim quotes of a paper about a topic, with their pages
+    Quotes {
+        #[clap(value_name = "KEY OR DOI")]
+        key: String,
+        /// Topic the quotes should be about
+        #[clap(value_name = "TOPIC")]
+        topic: String,
+        /// Number of quotes
+        #[clap(value_name = "LENGTH", short, long, default_value_t = 5)]
+        max: usize,
+        /// Print the quotes as json
+        #[clap(long, action)]
+        json: bool,
+        /// Search the paper again instead of using stored quotes
+        #[clap(long, action)]
+        refresh: bool,
+    },
+    /// One slide summing up a paper, for journal clubs
+    Card {
+        #[clap(value_name = "KEY OR DOI")]
+        key: String,
+        #[clap(long, default_value = "markdown", value_parser = ["markdown", "beamer"])]
+        format: String,
+        /// Number of stored quotes shown as takeaways
+        #[clap(long, default_value_t = 3)]
+        takeaways: usize,
+    },
+    /// Search the claims made in papers, extracted with --extract
+    Claims {
+        /// Claim to look for
+        #[clap(value_name = "CLAIM")]
+        query: Option<String>,
+        /// Find the claims of the papers that have none stored
+        #[clap(long, action)]
+        extract: bool,
+        /// Only extract the claims of this paper, again if they are stored
+        #[clap(long = "in", value_name = "KEY OR DOI")]
+        key: Option<String>,
+        /// Number of claims
+        #[clap(value_name = "LENGTH", short, long, default_value_t = 10)]
+        max: usize,
+    },
+    /// Serve the library to LLM clients over the Model Context Protocol
+    Mcp,
     /// Lists the references in the stack
     List {
         #[clap(value_name = "LENGTH", short, long)]
         max: Option<usize>,
+        /// Only list references with custom field key=value
+        #[clap(long = "field", short, value_name = "KEY=VALUE")]
+        fields: Vec<String>,
+        /// Order of the listing, rank uses the weights in the config and
+        /// recent is the order papers were last used
+        #[clap(
+            long,
+            default_value = "rank",
+            value_parser = ["rank", "recent", "author", "title", "year"]
+        )]
+        sort: String,
+    },
+    /// Exit with success if a key, doi or doi alias is in the library
+    Exists {
+        #[clap(value_name = "KEY OR DOI")]
+        key: String,
+    },
+    /// Count references in the stack matching a query
+    Count {
+        /// Text to look for in keys, titles and authors
+        #[clap(value_name = "QUERY", default_value_t = String::from(""))]
+        query: String,
+    },
+    /// Compare a bib file against the library
+    Check {
+        #[clap(value_name = "BIBFILE")]
+        path: String,
+    },
+    /// Find and repair problems in the stored library
+    Doctor {
+        /// Repair bibtex entries the parser rejects
+        #[clap(long, action)]
+        fix_bibtex: bool,
+        /// Accept every repair without asking
+        #[clap(long, short, action, requires = "fix_bibtex")]
+        yes: bool,
+    },
+    /// Check all stored bibtex for duplicates and missing fields
+    LintLibrary {
+        /// Also compare years, volumes and pages with doi.org
+        #[clap(long, action)]
+        online: bool,
+    },
+    /// Open access status of the references with a doi, and the journal's
+    /// self-archiving policy when sherpa_key is set in the config
+    OaStatus {
+        /// Query Unpaywall and Sherpa again instead of using cached results
+        #[clap(long, action)]
+        refresh: bool,
+    },
+    /// Copy a paper from the shared library into yours
+    CopyToLocal {
+        #[clap(value_name = "KEY OR DOI")]
+        key: String,
+    },
+    /// Find the papers inside a pdf of proceedings or merged scans
+    Split {
+        #[clap(value_name = "PDF")]
+        path: String,
+        /// Save every paper found as its own pdf next to the original
+        #[clap(long, action)]
+        write: bool,
+    },
+    /// Embed papers again after the embedding model changed
+    Reembed {
+        /// Embed every paper, not only missing and mismatched ones
+        #[clap(long, action)]
+        all: bool,
+        /// Print the papers that would be embedded
+        #[clap(long, action)]
+        dry_run: bool,
+    },
+    /// Search and replace with a regex in custom fields and stack names
+    Sed {
+        /// Substitution like s/old-project/new-project/g
+        #[clap(value_name = "EXPRESSION")]
+        expression: String,
+        /// Only rewrite this custom field, e.g. notes
+        #[clap(long = "field", short, value_name = "KEY")]
+        fields: Vec<String>,
+        /// Rewrite stack names, only them unless fields are given
+        #[clap(long, action)]
+        stacks: bool,
+        /// Print the changes without writing them
+        #[clap(long, action)]
+        dry_run: bool,
+    },
+    /// Empty the library after backing it up
+    Reset {
+        /// Really remove everything, asks to type the library name
+        #[clap(long, action)]
+        confirm: bool,
     },
     /// Export bib file
-    Export,
+    Export {
+        /// Only export references with custom field key=value
+        #[clap(long = "field", short, value_name = "KEY=VALUE")]
+        fields: Vec<String>,
+        /// Output format, csv for spreadsheet tools
+        #[clap(long, default_value = "bib", value_parser = ["bib", "csv"])]
+        format: String,
+        /// Csv columns, custom fields and bibtex fields can be used too
+        #[clap(
+            long,
+            value_delimiter = ',',
+            default_value = commands::export::DEFAULT_COLUMNS
+        )]
+        columns: Vec<String>,
+    },
+    /// Copy the pdfs not backed up yet to the backup directory
+    Backup {
+        /// Instead of the backup directory in the config
+        #[clap(long, value_name = "DIR")]
+        dir: Option<String>,
+    },
+    /// Bring back a paper's pdf from the backup directory
+    Restore {
+        #[clap(value_name = "KEY OR DOI")]
+        key: String,
+        #[clap(long, value_name = "DIR")]
+        dir: Option<String>,
+        /// Versions to go back from the latest backup
+        #[clap(long, default_value_t = 0)]
+        back: usize,
+        /// Overwrite a pdf that differs from the backup
+        #[clap(long, action)]
+        force: bool,
+    },
+    /// Create a throwaway library of sample papers to try bib on
+    Demo {
+        /// Where to create it, a temporary directory by default
+        #[clap(long, value_name = "DIR")]
+        dir: Option<String>,
+        /// Download the sample pdfs from arXiv
+        #[clap(long, action)]
+        pdfs: bool,
+    },
+    /// Share notes on papers with others and read theirs
+    Annotations {
+        #[command(subcommand)]
+        action: AnnotationAction,
+    },
+    /// Give every paper a citation key in one style
+    Rekey {
+        /// Key style, the one in the config by default
+        #[clap(long, value_parser = ["author-year", "author-year-word", "author-title", "scholar"])]
+        style: Option<String>,
+        /// Print the new keys without changing anything
+        #[clap(long, action)]
+        dry_run: bool,
+    },
+    /// Size of the library and when papers were opened
+    Stats,
+    /// Papers close to your work that you have not opened in a long time
+    Forgotten {
+        #[clap(value_name = "LENGTH", short, long, default_value_t = 10)]
+        max: usize,
+    },
+    /// Upcoming submission deadlines and the papers for them
+    Deadlines {
+        #[command(subcommand)]
+        action: Option<DeadlineAction>,
+        /// Include deadlines that have passed
+        #[clap(long, action)]
+        all: bool,
+    },
     /// Unset the current stack
     Unstack,
     /// Manage stacks
@@ -59,6 +386,56 @@ enum Commands {
         /// Stack subcommands (new, delete, rename)
         #[command(subcommand)]
         action: Option<StackAction>,
+
+        /// Print the planned changes without writing them
+        #[clap(long, action, global = true)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AnnotationAction {
+    /// Write the fields, quotes and claims of papers to a json bundle
+    Export {
+        /// Papers to share, the current stack by default
+        #[arg(value_name = "KEY OR DOI")]
+        keys: Vec<String>,
+        /// Name the bundle is signed with, the config email by default
+        #[clap(long, value_name = "NAME")]
+        by: Option<String>,
+        #[clap(long, short, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Add someone's bundle alongside your own notes
+    Import {
+        #[arg(value_name = "FILE")]
+        file: String,
+    },
+    /// Print what others noted about a paper
+    Show {
+        #[arg(value_name = "KEY OR DOI")]
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeadlineAction {
+    /// Track the deadline of a venue
+    Add {
+        /// Venue, papers in the stack of the same name are counted
+        #[arg(value_name = "VENUE")]
+        venue: String,
+        /// Deadline as YYYY-MM-DD
+        #[arg(value_name = "DATE")]
+        date: String,
+        /// Anything to remember, e.g. abstract due a week earlier
+        #[clap(long)]
+        note: Option<String>,
+    },
+    /// Stop tracking the deadline of a venue
+    Drop {
+        #[arg(value_name = "VENUE")]
+        venue: String,
     },
 }
 
@@ -97,46 +474,168 @@ enum StackAction {
     },
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    // The mcp server speaks json over stdout
+    if matches!(cli.command, Commands::Mcp) {
+        utils::fmt::log_to_stderr();
+    }
+    if cli.no_color || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        utils::fmt::disable_color();
+    }
+    if let Ok(config) = utils::io::read_config_file() {
+        utils::fmt::set_theme(config.ui);
+    }
+    if let Some(reason) = utils::io::probe_library() {
+        warn!("Read-only", "{}, only browsing and searching work", reason);
+    }
     let result = match cli.command {
-        Commands::Stack { name, action } => match (name, action) {
+        Commands::Stack {
+            name,
+            action,
+            dry_run,
+        } => match (name, action) {
             (None, None) => commands::stack::list(),
             (Some(stack), None) => commands::stack::switch(stack),
             (Some(stack), Some(StackAction::New)) => commands::stack::new(stack),
-            (Some(stack), Some(StackAction::Drop)) => commands::stack::drop(stack),
+            (Some(stack), Some(StackAction::Drop)) => commands::stack::drop(stack, dry_run),
             (Some(stack), Some(StackAction::Rename { new_name })) => {
-                commands::stack::rename(stack, new_name)
+                commands::stack::rename(stack, new_name, dry_run)
             }
             (Some(stack), Some(StackAction::Fork { new_stack })) => {
-                commands::stack::fork(stack, new_stack)
+                commands::stack::fork(stack, new_stack, dry_run)
             }
             (Some(stack), Some(StackAction::Merge { target })) => {
-                commands::stack::merge(target, stack)
+                commands::stack::merge(target, stack, dry_run)
             }
             (Some(stack), Some(StackAction::Toggle { query })) => {
                 commands::prompt::toggle(stack, query)
             }
             _ => Ok(println!("Invalid stack usage")),
         },
+        Commands::Backup { dir } => commands::backup::backup(dir),
+        Commands::Restore {
+            key,
+            dir,
+            back,
+            force,
+        } => commands::backup::restore(key, dir, back, force),
+        Commands::Demo { dir, pdfs } => commands::demo::demo(dir, pdfs),
+        Commands::Annotations { action } => match action {
+            AnnotationAction::Export { keys, by, output } => {
+                commands::annotations::export(keys, by, output)
+            }
+            AnnotationAction::Import { file } => commands::annotations::import(file),
+            AnnotationAction::Show { key } => commands::annotations::show(key),
+        },
+        Commands::Rekey { style, dry_run } => commands::rekey::rekey(style, dry_run),
+        Commands::Stats => commands::stats::stats(),
+        Commands::Forgotten { max } => commands::stats::forgotten(max),
+        Commands::Deadlines { action, all } => match action {
+            None => commands::deadlines::list(all),
+            Some(DeadlineAction::Add { venue, date, note }) => {
+                commands::deadlines::add(venue, date, note)
+            }
+            Some(DeadlineAction::Drop { venue }) => commands::deadlines::drop(venue),
+        },
         Commands::Unstack => commands::stack::unstack(),
-        Commands::Add { url, pdf, web } => commands::add::add(url, pdf, web),
+        Commands::Add {
+            stdin: true,
+            dry_run,
+            respect_tombstones,
+            ..
+        } => commands::add::add_stdin(dry_run, respect_tombstones),
+        Commands::Add {
+            url,
+            pdf,
+            web,
+            link,
+            respect_tombstones,
+            ..
+        } => commands::add::add(url, pdf, web, link, respect_tombstones),
+        Commands::Import {
+            dir,
+            link,
+            dry_run,
+            respect_tombstones,
+        } => commands::add::import(dir, link, dry_run, respect_tombstones),
         Commands::Open { query } => commands::prompt::open(query),
+        Commands::O { query } => commands::prompt::open_first(query),
+        Commands::Remove {
+            query,
+            yes,
+            dry_run,
+        } => commands::remove::remove(query, yes, dry_run),
         Commands::Yank { query } => commands::prompt::yank(query),
-        Commands::List { max } => commands::prompt::list(max),
-        Commands::Export => commands::export::export(),
+        Commands::Show { query } => commands::prompt::show(query),
+        Commands::Edit { query, fields } => commands::prompt::edit(query, fields),
+        Commands::Mine { query } => commands::prompt::mine(query),
+        Commands::Cv { since, format } => commands::cv::cv(since, format),
+        Commands::ReadingList {
+            stack,
+            format,
+            output,
+        } => commands::reading_list::reading_list(stack, format, output),
+        Commands::Cwyw { pandoc } => commands::prompt::cwyw(pandoc),
+        Commands::Find { query, key, max } => commands::find::find(query, key, max),
+        Commands::Quotes {
+            key,
+            topic,
+            max,
+            json,
+            refresh,
+        } => commands::quotes::quotes(key, topic, max, json, refresh),
+        Commands::Card {
+            key,
+            format,
+            takeaways,
+        } => commands::card::card(key, format, takeaways),
+        Commands::Claims {
+            query,
+            extract,
+            key,
+            max,
+        } => commands::claims::claims(query, extract, key, max),
+        Commands::Mcp => commands::mcp::serve(),
+        Commands::List { max, fields, sort } => commands::prompt::list(max, fields, sort),
+        Commands::Exists { key } => commands::lookup::exists(key),
+        Commands::Count { query } => commands::lookup::count(query),
+        Commands::Check { path } => commands::check::check(path),
+        Commands::Doctor { fix_bibtex, yes } => commands::doctor::doctor(fix_bibtex, yes),
+        Commands::LintLibrary { online } => commands::lint::lint_library(online),
+        Commands::OaStatus { refresh } => commands::oa::status(refresh),
+        Commands::CopyToLocal { key } => commands::shared::copy_to_local(key),
+        Commands::Split { path, write } => commands::split::split(path, write),
+        Commands::Reembed { all, dry_run } => commands::reembed::reembed(all, dry_run),
+        Commands::Sed {
+            expression,
+            fields,
+            stacks,
+            dry_run,
+        } => commands::sed::sed(expression, fields, stacks, dry_run),
+        Commands::Reset { confirm } => commands::reset::reset(confirm),
+        Commands::Export {
+            fields,
+            format,
+            columns,
+        } => commands::export::export(fields, format, columns),
     };
     match result {
-        Ok(()) => (),
-        Err(err) => erro(err.to_string()),
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            erro(err.to_string());
+            ExitCode::FAILURE
+        }
     }
 }
 
+/// Errors go to stderr, so scripts reading the output never parse them.
 fn erro(err: String) {
-    println!(
-        "{}error{}: {}",
-        color::Fg(color::Red),
-        color::Fg(color::Reset),
+    eprintln!(
+        "{}{}error{}: {}",
+        marker(Tone::Err),
+        paint(Tone::Err),
+        reset(),
         err
     );
 }
@@ -5,10 +5,8 @@ use anyhow::{anyhow, Result};
 use regex::Regex;
 use reqwest::blocking::get;
 use serde::Deserialize;
-use std::fs::File;
-use std::io::Write;
 
-const STOP_WORD: [&str; 34] = [
+pub const STOP_WORD: [&str; 34] = [
     "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
     "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
     "they", "this", "to", "was", "will", "and", "with",
@@ -62,6 +60,10 @@ fn get_arxiv_id(link: &str) -> Option<&str> {
     None
 }
 
+pub fn is_arxiv_link(link: &str) -> bool {
+    get_arxiv_id(link).is_some()
+}
+
 fn get_arxiv_pdf_link(arxiv_id: &str) -> String {
     format!("https://arxiv.org/pdf/{}.pdf", arxiv_id)
 }
@@ -171,10 +173,8 @@ fn generate_biblatex(entry: &Entry, arxiv_id: &str) -> String {
 pub fn download_pdf(pdf_url: &str, paper_id: &str) -> Result<Vec<u8>> {
     let response = get(pdf_url)?; // Use blocking `get`
     let filename = io::pdf_path(paper_id)?;
-    // Use blocking `File::create` and `write_all`
-    let mut file = File::create(&filename)?;
     let content = response.bytes()?;
-    file.write_all(&content)?;
+    io::write_atomic(&filename, &content)?;
 
     Ok(content.to_vec())
 }
@@ -1,7 +1,7 @@
-use crate::base::Paper;
+use crate::base::{Paper, PaperBuilder};
 use crate::utils::fmt::Clean;
 use anyhow::{anyhow, Result};
-use biblatex::{Bibliography, Entry, Person};
+use biblatex::{Bibliography, ChunksExt, Entry, Person};
 
 fn parse_year(entry: &Entry) -> Result<i64> {
     entry
@@ -14,11 +14,30 @@ fn parse_title(entry: &Entry) -> Result<String> {
         .get_as::<String>("title")
         .map_err(|e| anyhow!("Failed to title: {:?}", e))
 }
+/// Authors of the entry, or the editors of a volume that has none.
+fn creators(entry: &Entry) -> Option<Vec<Person>> {
+    ["author", "editor"]
+        .iter()
+        .filter_map(|field| entry.get_as::<Vec<Person>>(field).ok())
+        .find(|people| !people.is_empty())
+}
+
 fn parse_author(entry: &Entry) -> Result<String> {
-    entry
-        .get_as::<Vec<Person>>("author")
-        .map(|authors| format_authors(authors))
-        .map_err(|e| anyhow!("Failed to author: {:?}", e))
+    let authors = entry.get_as::<Vec<Person>>("author").ok();
+    if let Some(authors) = authors.filter(|authors| !authors.is_empty()) {
+        return Ok(format_authors(authors));
+    }
+    // Edited books and proceedings go by their editors, reports and
+    // manuals by the institution behind them
+    if let Some(editors) = creators(entry) {
+        return Ok(format!("{} (ed.)", format_authors(editors)));
+    }
+    ["institution", "organization", "school"]
+        .iter()
+        .find_map(|field| entry.get(field).map(|chunks| chunks.format_verbatim()))
+        .ok_or(anyhow!(
+            "Failed to author: no author, editor or institution"
+        ))
 }
 fn format_authors(authors: Vec<Person>) -> String {
     let formatted = match authors.len() {
@@ -43,7 +62,54 @@ fn format_authors(authors: Vec<Person>) -> String {
 //    }
 //}
 
-fn extract_entry(bibtex_str: &str) -> Result<Entry> {
+/// Strips resolver prefixes so dois can be compared.
+pub fn normalize_doi(doi: &str) -> String {
+    let doi = doi.trim().to_lowercase();
+    let doi = [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .fold(doi, |doi, prefix| {
+        doi.strip_prefix(prefix).map(str::to_string).unwrap_or(doi)
+    });
+    doi.trim().to_string()
+}
+
+/// Stable key derived from a doi, so references survive renaming their key.
+/// FNV-1a is used because it never changes between Rust releases.
+pub fn doi_alias(doi: &str) -> String {
+    let hash = normalize_doi(doi)
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("doi-{:010x}", hash >> 24)
+}
+
+/// Lowercases and drops punctuation and braces so titles can be compared.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Entry type in its bibtex spelling, e.g. article or phdthesis.
+pub fn entry_kind(entry: &Entry) -> String {
+    entry.entry_type.to_bibtex().to_string()
+}
+
+pub fn extract_entry(bibtex_str: &str) -> Result<Entry> {
     // Parse the bibliography (this will handle multiple entries, but we'll take the first one)
     let bibliography = Bibliography::parse(bibtex_str)
         .map_err(|e| anyhow!("Failed to parse BibTeX entry: {:?}", e))?;
@@ -55,19 +121,185 @@ fn extract_entry(bibtex_str: &str) -> Result<Entry> {
         .ok_or_else(|| anyhow!("No entries found in the provided BibTeX string"))
 }
 
+/// Leniently rebuilds an entry the parser rejects: markdown code fences and
+/// text around the entry are dropped, stray closing braces removed and
+/// missing ones added at the end. None if it still does not parse.
+pub fn repair_bibtex(bibtex: &str) -> Option<String> {
+    let unfenced = bibtex
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let start = unfenced.find('@')?;
+    let mut repaired = String::new();
+    let (mut depth, mut opened, mut escaped) = (0usize, false, false);
+    for c in unfenced[start..].chars() {
+        match c {
+            '{' if !escaped => {
+                depth += 1;
+                opened = true;
+            }
+            '}' if !escaped && depth == 0 => continue,
+            '}' if !escaped => depth -= 1,
+            _ => (),
+        }
+        escaped = !escaped && c == '\\';
+        repaired.push(c);
+        // Whatever follows the closed entry is commentary
+        if opened && depth == 0 {
+            break;
+        }
+    }
+    repaired.extend(std::iter::repeat_n('}', depth));
+    Paper::from_stored_bibtex(&repaired).ok().map(|_| repaired)
+}
+
+fn builder(bibtex: &str) -> Result<PaperBuilder> {
+    let entry = extract_entry(bibtex)?;
+    let title = parse_title(&entry)?.replace("\\n", "").replace("\\t", "");
+    let author = parse_author(&entry)?;
+    let year = parse_year(&entry)?;
+    Ok(PaperBuilder::new(&entry.key)
+        .author(&author)
+        .year(year)
+        .title(&title)
+        .kind(&entry_kind(&entry))
+        .bibtex(bibtex))
+}
+
 impl Paper {
+    /// Parses an entry coming into the library, refusing implausible ones.
     pub fn from_bibtex(bibtex: &str) -> Result<Self> {
-        let entry = extract_entry(bibtex)?;
-        let title = parse_title(&entry)?.replace("\\n", "").replace("\\t", "");
-        let author = parse_author(&entry)?;
-        let year = parse_year(&entry)?;
-        Ok(Paper {
-            id: entry.key.clone(),
-            author,
-            year,
-            title,
-            stack: Vec::new(),
-            bibtex: bibtex.to_owned(),
-        })
+        Ok(builder(bibtex)?.build()?)
+    }
+
+    /// Parses a rewrite of an entry already in the library, which is not
+    /// judged again so merges and repairs of older entries go through.
+    pub fn from_stored_bibtex(bibtex: &str) -> Result<Self> {
+        Ok(builder(bibtex)?.build_unchecked())
+    }
+
+    pub fn bibtex_field(&self, key: &str) -> Option<String> {
+        let entry = extract_entry(&self.bibtex).ok()?;
+        entry.get(key).map(|chunks| chunks.format_verbatim())
+    }
+
+    /// Every author as "Given Family", unlike the abbreviated `author`.
+    /// Editors stand in for the authors of edited volumes.
+    pub fn full_authors(&self) -> Vec<String> {
+        let entry = match extract_entry(&self.bibtex) {
+            Ok(entry) => entry,
+            Err(_) => return vec![self.author.clone()],
+        };
+        match creators(&entry) {
+            Some(authors) => authors
+                .iter()
+                .map(|person| format!("{} {}", person.given_name, person.name).clean())
+                .map(|name| name.trim().to_string())
+                .collect(),
+            None => vec![self.author.clone()],
+        }
+    }
+
+    /// First author as "Family Given", to sort by.
+    pub fn first_author(&self) -> String {
+        extract_entry(&self.bibtex)
+            .ok()
+            .and_then(|entry| creators(&entry))
+            .and_then(|authors| authors.into_iter().next())
+            .map(|person| format!("{} {}", person.name, person.given_name).clean())
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|| self.author.clone())
+    }
+
+    /// Family name of the first author, or editor of an edited volume,
+    /// without particles like "van".
+    pub fn family_name(&self) -> Option<String> {
+        extract_entry(&self.bibtex)
+            .ok()
+            .and_then(|entry| creators(&entry))
+            .and_then(|authors| authors.into_iter().next())
+            .map(|person| person.name.clean())
+    }
+
+    /// The same paper under another citation key, the bibtex included.
+    pub fn with_key(&self, key: &str) -> Paper {
+        let bibtex = match (self.bibtex.find('{'), self.bibtex.find(',')) {
+            (Some(open), Some(comma)) if open < comma => {
+                format!("{}{}{}", &self.bibtex[..=open], key, &self.bibtex[comma..])
+            }
+            _ => self.bibtex.clone(),
+        };
+        Paper {
+            id: key.to_string(),
+            bibtex,
+            ..self.clone()
+        }
+    }
+
+    pub fn doi(&self) -> Option<String> {
+        self.bibtex_field("doi").map(|doi| normalize_doi(&doi))
+    }
+
+    pub fn alias(&self) -> Option<String> {
+        self.doi().map(|doi| doi_alias(&doi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "@article{doe2020,\n  title = {A Study of {Things}},\n  author = {Doe, Jane},\n  year = {2020},\n}";
+
+    #[test]
+    fn repair_drops_fences_and_commentary() {
+        let fenced = format!(
+            "Here is the bibtex:\n```bibtex\n{}\n```\nLet me know if you need more.",
+            ENTRY
+        );
+        assert_eq!(repair_bibtex(&fenced).as_deref(), Some(ENTRY));
+    }
+
+    #[test]
+    fn repair_drops_stray_closing_braces() {
+        let leading = format!("}}\n{}\n}}}}", ENTRY);
+        assert_eq!(repair_bibtex(&leading).as_deref(), Some(ENTRY));
+        // A stray brace inside the entry closes it, keeping the fields before
+        let inside = ENTRY.replace("year = {2020}", "year = {2020}}");
+        let repaired = repair_bibtex(&inside).unwrap();
+        assert!(repaired.ends_with("year = {2020}}"));
+        assert_eq!(Paper::from_bibtex(&repaired).unwrap().year, 2020);
+    }
+
+    #[test]
+    fn repair_adds_missing_closing_braces() {
+        let truncated = "@article{doe2020,\n  title = {A Study of {Things}},\n  author = {Doe, Jane},\n  year = {2020";
+        assert!(Paper::from_bibtex(truncated).is_err());
+        let repaired = repair_bibtex(truncated).unwrap();
+        assert_eq!(repaired, format!("{}}}}}", truncated));
+        assert_eq!(Paper::from_bibtex(&repaired).unwrap().id, "doe2020");
+    }
+
+    #[test]
+    fn repair_keeps_escaped_braces() {
+        let escaped = ENTRY.replace("{Things}", "\\{Things\\}");
+        assert_eq!(repair_bibtex(&escaped).as_deref(), Some(escaped.as_str()));
+    }
+
+    #[test]
+    fn repair_gives_up_without_an_entry() {
+        assert_eq!(repair_bibtex("```\nno entry here\n```"), None);
+    }
+
+    #[test]
+    fn stored_entries_are_reparsed_without_judging_them() {
+        let ads = "@article{2019A&A...625A..10X,\n  title = {A Study of Things},\n  author = {Doe, Jane},\n  year = {2019},\n}";
+        assert_eq!(Paper::from_bibtex(ads).unwrap().id, "2019A&A...625A..10X");
+        let stored = "@book{Müller2020,\n  title = {Ein Buch},\n  author = {Müller, Jan},\n  year = {999},\n}";
+        assert!(Paper::from_bibtex(stored).is_err());
+        let paper = Paper::from_stored_bibtex(stored).unwrap();
+        assert_eq!((paper.id.as_str(), paper.year), ("Müller2020", 999));
+        assert_eq!(repair_bibtex(stored).as_deref(), Some(stored));
     }
 }
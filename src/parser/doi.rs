@@ -0,0 +1,15 @@
+use anyhow::{bail, Result};
+use reqwest::blocking::Client;
+use reqwest::header::ACCEPT;
+
+/// Asks doi.org for the bibtex of a doi through content negotiation.
+pub fn doi2bib(doi: &str) -> Result<String> {
+    let response = Client::new()
+        .get(format!("https://doi.org/{}", doi))
+        .header(ACCEPT, "application/x-bibtex")
+        .send()?;
+    if !response.status().is_success() {
+        bail!("doi.org returned {} for {}", response.status(), doi)
+    }
+    Ok(response.text()?.trim().to_string())
+}
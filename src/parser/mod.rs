@@ -1,2 +1,5 @@
 pub mod bibfile;
 pub mod arxiv;
+pub mod doi;
+pub mod unpaywall;
+pub mod sherpa;
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, bail, Result};
+use reqwest::blocking::get;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    #[serde(default)]
+    publisher_policy: Vec<Policy>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Policy {
+    #[serde(default)]
+    permitted_oa: Vec<Permission>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Permission {
+    #[serde(default)]
+    article_version: Vec<String>,
+    embargo: Option<Embargo>,
+    additional_oa_fee: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Embargo {
+    amount: u32,
+    units: String,
+}
+
+impl Embargo {
+    fn months(&self) -> u32 {
+        match self.units.as_str() {
+            "years" => self.amount * 12,
+            "weeks" => self.amount.div_ceil(4),
+            "days" => self.amount.div_ceil(30),
+            _ => self.amount,
+        }
+    }
+}
+
+fn request_url(issn: &str, key: &str) -> Result<Url> {
+    let filter = format!(r#"[["issn","equals","{}"]]"#, issn);
+    Url::parse_with_params(
+        "https://v2.sherpa.ac.uk/cgi/retrieve",
+        &[
+            ("item-type", "publication"),
+            ("format", "Json"),
+            ("api-key", key),
+            ("filter", &filter),
+        ],
+    )
+    .map_err(|e| anyhow!("Cannot build a sherpa url: {}", e))
+}
+
+/// Versions of a paper that may be self-archived without a fee, each with
+/// its shortest embargo, e.g. "submitted, accepted after 12 months".
+fn summarize(response: Response) -> Option<String> {
+    let item = response.items.into_iter().next()?;
+    let mut versions: BTreeMap<usize, u32> = BTreeMap::new();
+    let order = ["submitted", "accepted", "published"];
+    for permission in item
+        .publisher_policy
+        .iter()
+        .flat_map(|policy| policy.permitted_oa.iter())
+        .filter(|permission| permission.additional_oa_fee.as_deref() != Some("yes"))
+    {
+        let months = permission.embargo.as_ref().map_or(0, Embargo::months);
+        for version in permission.article_version.iter() {
+            if let Some(rank) = order.iter().position(|known| known == version) {
+                let shortest = versions.entry(rank).or_insert(months);
+                *shortest = (*shortest).min(months);
+            }
+        }
+    }
+    if versions.is_empty() {
+        return Some("no self-archiving without a fee".to_string());
+    }
+    let parts: Vec<String> = versions
+        .into_iter()
+        .map(|(rank, months)| match months {
+            0 => order[rank].to_string(),
+            months => format!("{} after {} months", order[rank], months),
+        })
+        .collect();
+    Some(parts.join(", "))
+}
+
+/// Self-archiving policy of the journal with the issn, None when Sherpa
+/// Romeo does not know the journal.
+pub fn policy(issn: &str, key: &str) -> Result<Option<String>> {
+    let response = get(request_url(issn, key)?)?;
+    if !response.status().is_success() {
+        bail!("Sherpa returned {} for {}", response.status(), issn)
+    }
+    Ok(summarize(response.json()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(json: &str) -> Option<String> {
+        summarize(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn summarizes_versions_with_shortest_embargo() {
+        let json = r#"{"items": [{"publisher_policy": [
+            {"permitted_oa": [
+                {"article_version": ["submitted"], "additional_oa_fee": "no"},
+                {"article_version": ["accepted"], "additional_oa_fee": "no",
+                 "embargo": {"amount": 1, "units": "years"}}
+            ]},
+            {"permitted_oa": [
+                {"article_version": ["accepted"], "additional_oa_fee": "no",
+                 "embargo": {"amount": 6, "units": "months"}},
+                {"article_version": ["published"], "additional_oa_fee": "yes"}
+            ]}
+        ]}]}"#;
+        assert_eq!(
+            summary(json).as_deref(),
+            Some("submitted, accepted after 6 months")
+        );
+    }
+
+    #[test]
+    fn reports_journals_without_free_archiving() {
+        let json = r#"{"items": [{"publisher_policy": [{"permitted_oa": [
+            {"article_version": ["published"], "additional_oa_fee": "yes"}
+        ]}]}]}"#;
+        assert_eq!(
+            summary(json).as_deref(),
+            Some("no self-archiving without a fee")
+        );
+    }
+
+    #[test]
+    fn unknown_journals_have_no_policy() {
+        assert_eq!(summary(r#"{"items": []}"#), None);
+    }
+
+    #[test]
+    fn escapes_the_filter() {
+        let url = request_url("0028-0836", "KEY").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://v2.sherpa.ac.uk/cgi/retrieve?item-type=publication&format=Json\
+             &api-key=KEY&filter=%5B%5B%22issn%22%2C%22equals%22%2C%220028-0836%22%5D%5D"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::utils;
+use crate::utils::clock;
+use anyhow::{anyhow, bail, Result};
+use bincode::{deserialize, serialize};
+use reqwest::blocking::get;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    is_oa: bool,
+    oa_status: Option<String>,
+    best_oa_location: Option<Location>,
+    journal_issn_l: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    url: Option<String>,
+    url_for_pdf: Option<String>,
+    version: Option<String>,
+    license: Option<String>,
+    host_type: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpenAccess {
+    pub is_oa: bool,
+    /// One of gold, green, hybrid, bronze or closed
+    pub status: String,
+    pub url: Option<String>,
+    /// Whether the best copy is the submitted, accepted or published version
+    pub version: Option<String>,
+    pub license: Option<String>,
+    /// Either publisher or repository
+    pub host: Option<String>,
+    /// Linking issn of the journal, to look up its policy
+    pub issn: Option<String>,
+    /// Self-archiving policy of the journal from Sherpa Romeo, when a key is set
+    pub policy: Option<String>,
+    pub checked: u64,
+}
+
+/// Api url of a doi. Dois may hold characters like ? # or <, so each part
+/// between slashes is escaped rather than pasted in.
+fn request_url(doi: &str, email: &str) -> Result<Url> {
+    let mut url = Url::parse("https://api.unpaywall.org/v2")?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Cannot build an unpaywall url"))?
+        .extend(doi.split('/'));
+    url.query_pairs_mut().append_pair("email", email);
+    Ok(url)
+}
+
+fn request(doi: &str, email: &str) -> Result<Option<Response>> {
+    let response = get(request_url(doi, email)?)?;
+    match response.status() {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if !status.is_success() => bail!("Unpaywall returned {} for {}", status, doi),
+        _ => Ok(Some(response.json()?)),
+    }
+}
+
+/// Link to the best open access pdf of a doi, if there is one.
+pub fn pdf_url(doi: &str, email: &str) -> Result<Option<String>> {
+    Ok(request(doi, email)?
+        .and_then(|response| response.best_oa_location)
+        .and_then(|location| location.url_for_pdf))
+}
+
+pub fn fetch(doi: &str, email: &str) -> Result<Option<OpenAccess>> {
+    let response = match request(doi, email)? {
+        Some(response) => response,
+        None => return Ok(None),
+    };
+    let location = response.best_oa_location;
+    Ok(Some(OpenAccess {
+        is_oa: response.is_oa,
+        status: response.oa_status.unwrap_or_else(|| "closed".to_string()),
+        url: location.as_ref().and_then(|l| l.url.clone()),
+        version: location.as_ref().and_then(|l| l.version.clone()),
+        license: location.as_ref().and_then(|l| l.license.clone()),
+        host: location.as_ref().and_then(|l| l.host_type.clone()),
+        issn: response.journal_issn_l,
+        policy: None,
+        checked: clock::now()?,
+    }))
+}
+
+pub fn save_cache(cache: &BTreeMap<String, OpenAccess>) -> Result<()> {
+    let encoded: Vec<u8> = serialize(cache)?;
+    let filename = utils::io::open_access_path()?;
+    utils::io::write_cache(&filename, &encoded)
+}
+
+pub fn load_cache() -> Result<BTreeMap<String, OpenAccess>> {
+    let filename = utils::io::open_access_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let mut file = File::open(filename)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    // A cache that no longer decodes is simply started over
+    Ok(deserialize(&buffer).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_dois_and_emails() {
+        assert_eq!(
+            request_url("10.1038/nature12373", "me@example.org")
+                .unwrap()
+                .as_str(),
+            "https://api.unpaywall.org/v2/10.1038/nature12373?email=me%40example.org"
+        );
+        assert_eq!(
+            request_url("10.1002/(SICI)1097#4<1::AID>3.0.CO;2-?", "a+b@example.org")
+                .unwrap()
+                .as_str(),
+            "https://api.unpaywall.org/v2/10.1002/(SICI)1097%234%3C1::AID%3E3.0.CO;2-%3F\
+             ?email=a%2Bb%40example.org"
+        );
+    }
+}
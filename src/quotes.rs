@@ -0,0 +1,46 @@
+use crate::utils;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A sentence copied verbatim from a paper, with the page it is on.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Quote {
+    pub text: String,
+    pub page: usize,
+    pub score: f32,
+}
+
+/// Quotes found in a paper about one topic.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TopicQuotes {
+    pub topic: String,
+    pub quotes: Vec<Quote>,
+    pub created: u64,
+}
+
+/// Quotes of each paper, by paper id.
+pub type Quotes = BTreeMap<String, Vec<TopicQuotes>>;
+
+/// Topics that only differ in case or spacing share their quotes.
+pub fn same_topic(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+    normalize(a).eq_ignore_ascii_case(&normalize(b))
+}
+
+pub fn save_quotes(quotes: &Quotes) -> Result<()> {
+    let encoded: Vec<u8> = serialize(quotes)?;
+    let filename = utils::io::quotes_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_quotes() -> Result<Quotes> {
+    let filename = utils::io::quotes_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Quotes = deserialize(&buffer)?;
+    Ok(decoded)
+}
@@ -0,0 +1,203 @@
+use crate::base::Paper;
+use crate::utils::collate::collation_key;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+
+/// Weights of the signals that order listings and searches, from the
+/// `[ranking]` table of the config. The defaults keep the library order when
+/// listing and put the closest papers first when searching.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Weights {
+    /// Similarity of the paper to the search query
+    #[serde(default = "default_similarity")]
+    pub similarity: f32,
+    /// How recently the paper was added or used
+    #[serde(default)]
+    pub recency: f32,
+    /// How recently the paper was published
+    #[serde(default)]
+    pub year: f32,
+    /// Whether the paper is marked as yours
+    #[serde(default)]
+    pub mine: f32,
+}
+
+fn default_similarity() -> f32 {
+    1.0
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            similarity: default_similarity(),
+            recency: 0.0,
+            year: 0.0,
+            mine: 0.0,
+        }
+    }
+}
+
+/// Orders the papers by their weighted score, best first. Papers with equal
+/// scores keep their library order. Every signal is scaled to about 0..1 so
+/// the weights are comparable.
+pub fn rank(
+    ids: &[String],
+    papers: &IndexMap<String, Paper>,
+    similarity: &BTreeMap<String, f32>,
+    mine: &[String],
+    weights: &Weights,
+) -> Vec<String> {
+    let years = ids.iter().filter_map(|id| papers.get(id)).map(|p| p.year);
+    let (oldest, newest) = years.fold((i64::MAX, i64::MIN), |(lo, hi), year| {
+        (lo.min(year), hi.max(year))
+    });
+    let span = (newest - oldest).max(1) as f32;
+    let last = papers.len().saturating_sub(1).max(1) as f32;
+
+    let score = |id: &String| -> f32 {
+        let paper = match papers.get(id) {
+            Some(paper) => paper,
+            None => return f32::MIN,
+        };
+        let recency = papers
+            .get_index_of(id)
+            .map_or(0.0, |position| 1.0 - position as f32 / last);
+        let year = (paper.year - oldest) as f32 / span;
+        let mine = mine.contains(id) as u8 as f32;
+        weights.similarity * similarity.get(id).copied().unwrap_or(0.0)
+            + weights.recency * recency
+            + weights.year * year
+            + weights.mine * mine
+    };
+    let mut scored: Vec<(f32, &String)> = ids.iter().map(|id| (score(id), id)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().map(|(_, id)| id.clone()).collect()
+}
+
+/// How well the query matches the key, title or authors, from 0 to 1. Every
+/// query word is compared with its closest word, so typos still match.
+pub fn fuzzy_score(query: &str, paper: &Paper) -> f32 {
+    if query.eq_ignore_ascii_case(&paper.id) {
+        return 1.0;
+    }
+    let words = |text: &str| -> Vec<String> {
+        collation_key(text)
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let query_words = words(query);
+    let candidate = format!("{} {} {}", paper.id, paper.title, paper.author);
+    let candidate_words = words(&candidate);
+    if query_words.is_empty() || candidate_words.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = query_words
+        .iter()
+        .map(|word| {
+            candidate_words
+                .iter()
+                .map(|other| strsim::jaro_winkler(word, other))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    (total / query_words.len() as f64) as f32
+}
+
+/// Best fuzzy matches of the query with their scores, for searching without
+/// embeddings.
+pub fn fuzzy_nearest(
+    query: &str,
+    papers: &IndexMap<String, Paper>,
+    ids: &[String],
+    k: usize,
+) -> Vec<(String, f32)> {
+    let scored = ids
+        .iter()
+        .filter_map(|id| papers.get(id))
+        .map(|paper| (paper.id.clone(), fuzzy_score(query, paper)));
+    top_k(scored, k)
+}
+
+/// An item with its score, ordered so the lowest score tops a `BinaryHeap`.
+struct Scored<T> {
+    item: T,
+    score: f32,
+}
+
+impl<T> PartialEq for Scored<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score.total_cmp(&other.score).is_eq()
+    }
+}
+
+impl<T> Eq for Scored<T> {}
+
+impl<T> Ord for Scored<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score)
+    }
+}
+
+impl<T> PartialOrd for Scored<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The k highest scored items, best first. Only k items are held at a time,
+/// so the scores can be streamed without collecting and sorting them all.
+/// Items scored NaN are left out.
+pub fn top_k<T>(items: impl IntoIterator<Item = (T, f32)>, k: usize) -> Vec<(T, f32)> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for (item, score) in items {
+        if score.is_nan() {
+            continue;
+        }
+        if heap.len() < k {
+            heap.push(Scored { item, score });
+        } else if heap.peek().is_some_and(|worst| score > worst.score) {
+            heap.pop();
+            heap.push(Scored { item, score });
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|scored| (scored.item, scored.score))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_k_keeps_the_best_first() {
+        let scored = vec![("a", 0.2), ("b", 0.9), ("c", -1.0), ("d", 0.5), ("e", 0.7)];
+        assert_eq!(
+            top_k(scored.clone(), 3),
+            vec![("b", 0.9), ("e", 0.7), ("d", 0.5)]
+        );
+        assert_eq!(top_k(scored.clone(), 10).len(), 5);
+        assert_eq!(top_k(scored, 10).last(), Some(&("c", -1.0)));
+    }
+
+    #[test]
+    fn top_k_of_nothing_is_empty() {
+        assert!(top_k(vec![("a", 1.0)], 0).is_empty());
+        assert!(top_k(Vec::<(&str, f32)>::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn top_k_leaves_out_nan() {
+        let scored = vec![("a", f32::NAN), ("b", 0.1), ("c", f32::NAN), ("d", 0.3)];
+        assert_eq!(top_k(scored.clone(), 1), vec![("d", 0.3)]);
+        assert_eq!(top_k(scored, 4), vec![("d", 0.3), ("b", 0.1)]);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::utils;
+use crate::utils::clock;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Bibtex of a paper before and after an automated rewrite.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Revision {
+    /// What rewrote the entry, e.g. doctor
+    pub by: String,
+    pub before: String,
+    pub after: String,
+    pub at: u64,
+}
+
+/// Rewrites of each paper, by paper id, oldest first.
+pub type Revisions = BTreeMap<String, Vec<Revision>>;
+
+impl Revision {
+    pub fn new(by: &str, before: &str, after: &str) -> Result<Self> {
+        Ok(Revision {
+            by: by.to_string(),
+            before: before.to_string(),
+            after: after.to_string(),
+            at: clock::now()?,
+        })
+    }
+}
+
+/// Keeps the old and new bibtex so a rewrite can always be undone by hand.
+/// Called once the rewritten papers are saved, so only applied rewrites
+/// are kept.
+pub fn record(pending: Revisions) -> Result<()> {
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut revisions = load_revisions()?;
+    for (id, new) in pending {
+        revisions.entry(id).or_default().extend(new);
+    }
+    save_revisions(&revisions)
+}
+
+pub fn save_revisions(revisions: &Revisions) -> Result<()> {
+    let encoded: Vec<u8> = serialize(revisions)?;
+    let filename = utils::io::revisions_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_revisions() -> Result<Revisions> {
+    let filename = utils::io::revisions_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Revisions = deserialize(&buffer)?;
+    Ok(decoded)
+}
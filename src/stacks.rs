@@ -1,3 +1,4 @@
+use crate::utils::fmt::{color_enabled, palette, reset, Palette};
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -23,9 +24,25 @@ pub struct Stack {
     pub color: String,
 }
 
-impl fmt::Display for Stack {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let stack_color: &dyn Color = match self.color.to_lowercase().as_str() {
+impl Stack {
+    fn paint(&self) -> String {
+        if !color_enabled() {
+            return String::new();
+        }
+        let name = self.color.to_lowercase();
+        if palette() == Palette::ColorBlind {
+            let (r, g, b) = match name.as_str() {
+                "yellow" | "light_yellow" => (240, 228, 66),
+                "blue" => (0, 114, 178),
+                "green" | "light_green" => (0, 158, 115),
+                "cyan" | "light_cyan" => (86, 180, 233),
+                "light_red" => (230, 159, 0),
+                "light_blue" => (204, 121, 167),
+                _ => (213, 94, 0),
+            };
+            return color::Fg(color::Rgb(r, g, b)).to_string();
+        }
+        let stack_color: &dyn Color = match name.as_str() {
             "red" => &color::Red,
             "yellow" => &color::Yellow,
             "blue" => &color::Blue,
@@ -35,16 +52,16 @@ impl fmt::Display for Stack {
             "light_yellow" => &color::LightYellow,
             "light_blue" => &color::LightBlue,
             "light_green" => &color::LightGreen,
-            "ligh_cyan" => &color::LightCyan,
+            "light_cyan" => &color::LightCyan,
             _ => &color::Red, // default to Red if color name is unrecognized
         };
-        write!(
-            f,
-            "{}[{}]{}",
-            color::Fg(stack_color),
-            self.name,
-            color::Fg(color::Reset)
-        )
+        color::Fg(stack_color).to_string()
+    }
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]{}", self.paint(), self.name, reset())
     }
 }
 
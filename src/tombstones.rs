@@ -0,0 +1,117 @@
+use crate::base::Paper;
+use crate::parser::bibfile::normalize_title;
+use crate::utils;
+use crate::utils::clock;
+use anyhow::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What is remembered of a paper that was deliberately removed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tombstone {
+    pub doi: Option<String>,
+    pub title: String,
+    pub removed: u64,
+}
+
+/// Removed papers, by paper id.
+pub type Tombstones = BTreeMap<String, Tombstone>;
+
+impl Tombstone {
+    pub fn of(paper: &Paper) -> Result<Self> {
+        Ok(Tombstone {
+            doi: paper.doi(),
+            title: normalize_title(&paper.title),
+            removed: clock::now()?,
+        })
+    }
+}
+
+/// Key of the removed paper the given one is. Dois are compared when both
+/// have one, titles only otherwise, so papers sharing a generic title or an
+/// author-year key are not taken for each other.
+pub fn find_tombstone(tombstones: &Tombstones, paper: &Paper) -> Option<String> {
+    let doi = paper.doi();
+    let title = normalize_title(&paper.title);
+    tombstones
+        .iter()
+        .find(|(_, tombstone)| match (&tombstone.doi, &doi) {
+            (Some(removed), Some(doi)) => removed == doi,
+            _ => tombstone.title == title,
+        })
+        .map(|(id, _)| id.clone())
+}
+
+pub fn save_tombstones(tombstones: &Tombstones) -> Result<()> {
+    let encoded: Vec<u8> = serialize(tombstones)?;
+    let filename = utils::io::tombstones_path()?;
+    utils::io::write_atomic(&filename, &encoded)
+}
+
+pub fn load_tombstones() -> Result<Tombstones> {
+    let filename = utils::io::tombstones_path()?;
+    if !filename.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let buffer = std::fs::read(filename)?;
+    let decoded: Tombstones = deserialize(&buffer)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::{with_clock, FixedClock};
+
+    fn paper(key: &str, title: &str, doi: Option<&str>) -> Paper {
+        let doi = doi.map_or(String::new(), |doi| format!(", doi = {{{}}}", doi));
+        Paper::from_bibtex(&format!(
+            "@article{{{}, title = {{{}}}, author = {{Doe, Jane}}, year = {{2020}}{}}}",
+            key, title, doi
+        ))
+        .unwrap()
+    }
+
+    fn removed(paper: &Paper) -> Tombstones {
+        BTreeMap::from([(paper.id.clone(), Tombstone::of(paper).unwrap())])
+    }
+
+    #[test]
+    fn matches_by_doi_when_both_have_one() {
+        let tombstones = removed(&paper("doe2020", "Editorial", Some("10.1/a")));
+        let same = paper("other", "Editorial Note", Some("https://doi.org/10.1/A"));
+        assert_eq!(
+            find_tombstone(&tombstones, &same).as_deref(),
+            Some("doe2020")
+        );
+        let different = paper("doe2020", "Editorial", Some("10.1/b"));
+        assert_eq!(find_tombstone(&tombstones, &different), None);
+    }
+
+    #[test]
+    fn falls_back_to_titles_without_a_doi() {
+        let tombstones = removed(&paper("doe2020", "A Study of Things", None));
+        let same = paper("x", "A study of things", Some("10.1/a"));
+        assert_eq!(
+            find_tombstone(&tombstones, &same).as_deref(),
+            Some("doe2020")
+        );
+        let different = paper("doe2020", "Another Study", None);
+        assert_eq!(find_tombstone(&tombstones, &different), None);
+    }
+
+    #[test]
+    fn tombstone_snapshot_is_stable_under_a_fixed_clock() {
+        let paper = Paper::from_bibtex(
+            "@article{doe2020, title = {A {Study} of Things}, author = {Doe, Jane}, \
+             year = {2020}, doi = {10.1000/XYZ}}",
+        )
+        .unwrap();
+        let tombstone = with_clock(FixedClock(1_700_000_000), || Tombstone::of(&paper)).unwrap();
+        assert_eq!(
+            serde_json::to_string(&tombstone).unwrap(),
+            r#"{"doi":"10.1000/xyz","title":"a study of things","removed":1700000000}"#
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where timestamps come from, swappable so tests and reproducible runs write
+/// the same stores every time.
+pub trait Clock {
+    /// Seconds since the unix epoch.
+    fn now(&self) -> Result<u64>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<u64> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+}
+
+/// Always the same time.
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Result<u64> {
+        Ok(self.0)
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Option<Box<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with every timestamp taken from `clock`.
+#[cfg(test)]
+pub fn with_clock<T>(clock: impl Clock + 'static, f: impl FnOnce() -> T) -> T {
+    let previous = CLOCK.with(|current| current.replace(Some(Box::new(clock))));
+    let result = f();
+    CLOCK.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Seconds since the unix epoch, for every timestamp the library stores.
+/// BIB_FIXED_CLOCK pins it to a unix timestamp for reproducible runs.
+pub fn now() -> Result<u64> {
+    if let Some(time) = CLOCK.with(|current| current.borrow().as_ref().map(|clock| clock.now())) {
+        return time;
+    }
+    if let Ok(fixed) = env::var("BIB_FIXED_CLOCK") {
+        let fixed = fixed
+            .trim()
+            .parse()
+            .context("BIB_FIXED_CLOCK must be a unix timestamp")?;
+        return FixedClock(fixed).now();
+    }
+    SystemClock.now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injected_clock_is_used_and_restored() {
+        assert_eq!(with_clock(FixedClock(42), || now().unwrap()), 42);
+        let nested = with_clock(FixedClock(1), || {
+            with_clock(FixedClock(2), || now().unwrap()) + now().unwrap()
+        });
+        assert_eq!(nested, 3);
+        assert!(now().unwrap() > 42);
+    }
+}
@@ -0,0 +1,39 @@
+use std::cmp::Ordering;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Letters that do not decompose into a base letter and accents.
+fn fold_letter(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' => "ss",
+        'æ' | 'Æ' => "ae",
+        'œ' | 'Œ' => "oe",
+        'ø' | 'Ø' => "o",
+        'ł' | 'Ł' => "l",
+        'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+        'þ' | 'Þ' => "th",
+        'ı' => "i",
+        _ => return None,
+    })
+}
+
+/// Key that sorts names and titles the way a reader expects: accents and case
+/// only matter when everything else is equal, so Ångström sits with the A's.
+/// Other scripts keep their own order after the Latin one.
+pub fn collation_key(text: &str) -> String {
+    let mut key = String::with_capacity(text.len());
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        match fold_letter(c) {
+            Some(folded) => key.push_str(folded),
+            None => key.extend(c.to_lowercase()),
+        }
+    }
+    key
+}
+
+/// Compares by collation key, falling back to the text itself for a stable order.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    collation_key(a)
+        .cmp(&collation_key(b))
+        .then_with(|| a.cmp(b))
+}
@@ -0,0 +1,128 @@
+use crate::parser::bibfile::extract_entry;
+use crate::utils::fmt::{paint, reset, Tone};
+use anyhow::{anyhow, Result};
+use biblatex::ChunksExt;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl FieldChange {
+    pub fn print(&self) {
+        println!("{:>12}", self.field);
+        if let Some(old) = &self.old {
+            println!("{:>12} {}- {}{}", "", paint(Tone::Err), old, reset());
+        }
+        if let Some(new) = &self.new {
+            println!("{:>12} {}+ {}{}", "", paint(Tone::Ok), new, reset());
+        }
+    }
+}
+
+/// Lists the fields that differ between two bibtex entries.
+pub fn field_changes(old: &str, new: &str) -> Result<Vec<FieldChange>> {
+    let old = extract_entry(old)?;
+    let new = extract_entry(new)?;
+    let fields: BTreeSet<&String> = old.fields.keys().chain(new.fields.keys()).collect();
+    Ok(fields
+        .into_iter()
+        .filter_map(|field| {
+            let old = old.get(field).map(|chunks| chunks.format_verbatim());
+            let new = new.get(field).map(|chunks| chunks.format_verbatim());
+            (old != new).then(|| FieldChange {
+                field: field.clone(),
+                old,
+                new,
+            })
+        })
+        .collect())
+}
+
+/// Applies the changes to the old entry for which `take_new` holds.
+pub fn merge<F>(old: &str, new: &str, mut take_new: F) -> Result<String>
+where
+    F: FnMut(&FieldChange) -> Result<bool>,
+{
+    let changes = field_changes(old, new)?;
+    let mut merged = extract_entry(old)?;
+    let new = extract_entry(new)?;
+    let mut changed = false;
+    for change in changes.iter() {
+        if !take_new(change)? {
+            continue;
+        }
+        changed = true;
+        match new.fields.get(&change.field) {
+            Some(chunks) => merged.set(&change.field, chunks.clone()),
+            None => {
+                merged.remove(&change.field);
+            }
+        }
+    }
+    if !changed {
+        return Ok(old.to_string());
+    }
+    merged
+        .to_bibtex_string()
+        .map_err(|e| anyhow!("Failed to write bibtex: {:?}", e))
+}
+
+/// Asks which side wins for every differing field.
+pub fn resolve(old: &str, new: &str) -> Result<String> {
+    merge(old, new, |change| {
+        change.print();
+        loop {
+            print!("{:>12} keep [o]ld or take [n]ew? ", "");
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim() {
+                "o" | "O" | "" => return Ok(false),
+                "n" | "N" => return Ok(true),
+                _ => continue,
+            }
+        }
+    })
+}
+
+/// Prints what an automated rewrite changes, field by field when both
+/// entries parse and line by line otherwise.
+pub fn print_rewrite(old: &str, new: &str) {
+    if let Ok(changes) = field_changes(old, new) {
+        changes.iter().for_each(FieldChange::print);
+        return;
+    }
+    let lines = |text: &str| -> BTreeSet<String> {
+        text.lines().map(str::trim).map(String::from).collect()
+    };
+    let (old_lines, new_lines) = (lines(old), lines(new));
+    for line in old.lines().filter(|line| !new_lines.contains(line.trim())) {
+        println!("{:>12} {}- {}{}", "", paint(Tone::Err), line, reset());
+    }
+    for line in new.lines().filter(|line| !old_lines.contains(line.trim())) {
+        println!("{:>12} {}+ {}{}", "", paint(Tone::Ok), line, reset());
+    }
+}
+
+/// Shows an automated rewrite and asks whether to keep it, unless `yes`.
+pub fn accept_rewrite(old: &str, new: &str, yes: bool) -> Result<bool> {
+    print_rewrite(old, new);
+    if yes {
+        return Ok(true);
+    }
+    loop {
+        print!("{:>12} [a]ccept or [r]eject? ", "");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        match answer.trim() {
+            "a" | "A" => return Ok(true),
+            "r" | "R" | "" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use termion::color::{self, Fg, Rgb};
+
 pub trait Clean {
     fn clean(&self) -> Self;
 }
@@ -12,11 +16,225 @@ impl Clean for String {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    #[default]
+    Default,
+    /// Okabe-Ito colors, distinguishable under the common color blindnesses
+    ColorBlind,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ellipsis {
+    /// Cut the end of long titles
+    #[default]
+    End,
+    /// Keep the start and the end of long titles
+    Middle,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Theme {
+    #[serde(default)]
+    pub palette: Palette,
+    /// Prefix status lines with [ok], [warn] and [err]
+    #[serde(default)]
+    pub ascii: bool,
+    #[serde(default)]
+    pub ellipsis: Ellipsis,
+    /// Continue long titles on a second line instead of cutting them
+    #[serde(default)]
+    pub wrap: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum Tone {
+    Ok,
+    Warn,
+    Err,
+    Muted,
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+static COLOR: OnceLock<bool> = OnceLock::new();
+static STDERR: OnceLock<bool> = OnceLock::new();
+
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// Turns every ANSI color code off, for logs, CI and screen readers.
+pub fn disable_color() {
+    let _ = COLOR.set(false);
+}
+
+pub fn color_enabled() -> bool {
+    *COLOR.get_or_init(|| true)
+}
+
+/// Sends progress and warnings to stderr, for when stdout carries a
+/// protocol like the mcp server's.
+pub fn log_to_stderr() {
+    let _ = STDERR.set(true);
+}
+
+pub fn logs_to_stderr() -> bool {
+    *STDERR.get_or_init(|| false)
+}
+
+fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+pub fn palette() -> Palette {
+    theme().palette
+}
+
+pub fn ellipsis() -> Ellipsis {
+    theme().ellipsis
+}
+
+pub fn wrap() -> bool {
+    theme().wrap
+}
+
+pub fn paint(tone: Tone) -> String {
+    if !color_enabled() {
+        return String::new();
+    }
+    match (palette(), tone) {
+        (Palette::Default, Tone::Ok) => Fg(color::Green).to_string(),
+        (Palette::Default, Tone::Warn) => Fg(color::Yellow).to_string(),
+        (Palette::Default, Tone::Err) => Fg(color::Red).to_string(),
+        (Palette::ColorBlind, Tone::Ok) => Fg(Rgb(0, 114, 178)).to_string(),
+        (Palette::ColorBlind, Tone::Warn) => Fg(Rgb(230, 159, 0)).to_string(),
+        (Palette::ColorBlind, Tone::Err) => Fg(Rgb(213, 94, 0)).to_string(),
+        (_, Tone::Muted) => Fg(Rgb(83, 110, 122)).to_string(),
+    }
+}
+
+pub fn reset() -> String {
+    if !color_enabled() {
+        return String::new();
+    }
+    Fg(color::Reset).to_string()
+}
+
+pub fn marker(tone: Tone) -> &'static str {
+    if !theme().ascii {
+        return "";
+    }
+    match tone {
+        Tone::Ok => "[ok]   ",
+        Tone::Warn => "[warn] ",
+        Tone::Err => "[err]  ",
+        Tone::Muted => "",
+    }
+}
+
+/// Calendar date of a unix timestamp as YYYY-MM-DD, in UTC.
+pub fn date(secs: u64) -> String {
+    // Civil from days, shifting the year to start in March
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Unix timestamp of midnight UTC on a YYYY-MM-DD date, the inverse of `date`.
+pub fn parse_date(text: &str) -> Option<u64> {
+    let mut parts = text
+        .trim()
+        .splitn(3, '-')
+        .map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days from civil, with years starting in March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let secs = u64::try_from(days * 86400).ok()?;
+    // Reject dates like February 30 that roll over into the next month
+    (date(secs) == text.trim()).then_some(secs)
+}
+
 #[macro_export]
 macro_rules! blog {
     ($category:expr, $($arg:tt)*) => {{
-        use termion::color;
+        use $crate::utils::fmt::{marker, paint, reset, Tone};
         let formatted_args = format!($($arg)*);
-        println!("{}{:>12}{} {}",color::Fg(color::Green), $category,color::Fg(color::Reset), formatted_args);
+        let line = format!("{}{}{:>12}{} {}", marker(Tone::Ok), paint(Tone::Ok), $category, reset(), formatted_args);
+        match $crate::utils::fmt::logs_to_stderr() {
+            true => eprintln!("{}", line),
+            false => println!("{}", line),
+        }
     }};
 }
+
+#[macro_export]
+macro_rules! warn {
+    ($category:expr, $($arg:tt)*) => {{
+        use $crate::utils::fmt::{marker, paint, reset, Tone};
+        let formatted_args = format!($($arg)*);
+        let line = format!("{}{}{:>12}{} {}", marker(Tone::Warn), paint(Tone::Warn), $category, reset(), formatted_args);
+        match $crate::utils::fmt::logs_to_stderr() {
+            true => eprintln!("{}", line),
+            false => println!("{}", line),
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats_known_days() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_700_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn parse_date_round_trips() {
+        for text in ["1970-01-01", "2000-02-29", "2024-12-31", "2100-03-01"] {
+            assert_eq!(date(parse_date(text).unwrap()), text);
+        }
+        assert_eq!(parse_date(" 2023-11-14 "), Some(1_699_920_000));
+    }
+
+    #[test]
+    fn parse_date_rejects_impossible_dates() {
+        for text in [
+            "2023-02-30",
+            "2023-02-29",
+            "1900-02-29",
+            "2023-13-01",
+            "2023-00-10",
+        ] {
+            assert_eq!(parse_date(text), None, "{}", text);
+        }
+        for text in ["", "2023", "2023-1", "tomorrow", "1969-12-31"] {
+            assert_eq!(parse_date(text), None, "{}", text);
+        }
+    }
+}
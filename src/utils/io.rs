@@ -1,22 +1,134 @@
+use crate::embedding::Model;
+use crate::keys::KeyStyle;
+use crate::ranking::Weights;
 use crate::stacks::Stack;
-use anyhow::Result;
+use crate::utils::fmt::Theme;
+use crate::warn;
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
 use shellexpand::tilde;
 use std::fs::{self, File};
-use std::io::{BufReader, Read, Write};
-use std::path::PathBuf;
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub stack: String,
+    /// Contact email sent to the Unpaywall API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Sherpa Romeo API key, to report journals' self-archiving policies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sherpa_key: Option<String>,
+    /// Directory of a read-only library shown alongside this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared: Option<String>,
+    /// Directory pdfs are backed up to, e.g. a mounted drive or synced folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    /// Keys of the papers authored by the user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mine: Vec<String>,
+    /// Match score, 0 to 1, from which bib o opens a paper without asking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_threshold: Option<f32>,
+    /// Key style given to added papers, which keep the source's key when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_style: Option<KeyStyle>,
     pub stacks: Vec<Stack>,
+    /// Submission deadlines being tracked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deadlines: Vec<Deadline>,
+    #[serde(default)]
+    pub ui: Theme,
+    #[serde(default)]
+    pub ranking: Weights,
+    #[serde(default)]
+    pub features: Features,
+}
+
+/// A venue's submission deadline. Papers in the stack with the venue's name
+/// are the ones being written for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Deadline {
+    pub venue: String,
+    /// As YYYY-MM-DD
+    pub date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Switches for what runs the embedding model, for machines where it is too
+/// slow or cannot be downloaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Features {
+    /// Embed pdfs on add and search by meaning, otherwise searches match words
+    #[serde(default = "enabled")]
+    pub embeddings: bool,
+    /// Page level search of bib find --in and bib quotes
+    #[serde(default = "enabled")]
+    pub deep_find: bool,
+    /// Embed hashed words instead of running the model, as the demo library
+    /// does. Nothing is downloaded, but searches only match words.
+    #[serde(default)]
+    pub synthetic: bool,
+    /// english, or multilingual to search in other languages than the papers
+    #[serde(default)]
+    pub model: Model,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            embeddings: true,
+            deep_find: true,
+            synthetic: false,
+            model: Model::default(),
+        }
+    }
+}
+
+impl Features {
+    /// Fails with the config switch to flip when embeddings are turned off.
+    pub fn require_embeddings(&self, what: &str) -> Result<()> {
+        if !self.embeddings {
+            bail!(
+                "{} needs embeddings, turned off by features.embeddings in ~/.bib/config.toml",
+                what
+            )
+        }
+        Ok(())
+    }
+    pub fn require_deep_find(&self) -> Result<()> {
+        self.require_embeddings("Searching pages")?;
+        if !self.deep_find {
+            bail!("Searching pages is turned off by features.deep_find in ~/.bib/config.toml")
+        }
+        Ok(())
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             stack: "all".to_string(),
+            email: None,
+            sherpa_key: None,
+            shared: None,
+            backup: None,
+            mine: Vec::new(),
+            open_threshold: None,
+            key_style: None,
             stacks: Vec::default(),
+            deadlines: Vec::new(),
+            ui: Theme::default(),
+            ranking: Weights::default(),
+            features: Features::default(),
         }
     }
 }
@@ -29,10 +141,17 @@ impl Config {
     }
 }
 
+/// Where the library lives, ~/.bib unless BIB_HOME points elsewhere.
+fn library_dir() -> PathBuf {
+    match std::env::var_os("BIB_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(tilde("~/.bib").to_string()),
+    }
+}
+
 pub fn read_config_file() -> Result<Config> {
     // Base directory
-    let base_dir = tilde("~/.bib").to_string();
-    let mut config_path = PathBuf::from(&base_dir);
+    let mut config_path = library_dir();
     // Make sure the directories exist
     fs::create_dir_all(&config_path)?;
     config_path.push("config.toml");
@@ -56,19 +175,104 @@ pub fn save_config_file(config: &Config) -> Result<()> {
     // Serialize the Config struct to TOML
     let toml_content = toml::to_string_pretty(config)?;
     // Create the directory if it doesn't exist
-    let dir = tilde("~/.bib").to_string();
+    let dir = library_dir();
     fs::create_dir_all(&dir)?;
     // Create and write to the config.toml file
-    let file_path = dir + "/config.toml";
-    let mut file = fs::File::create(&file_path)?;
-    file.write_all(toml_content.as_bytes())?;
+    let file_path = dir.join("config.toml");
+    write_atomic(&file_path, toml_content.as_bytes())
+}
+
+/// Why the library cannot be written, once a probe has found it so.
+static READ_ONLY: OnceLock<String> = OnceLock::new();
+
+/// Turns a failed write into an explanation of what is wrong with the disk,
+/// passing other errors on.
+fn storage_error(err: io::Error, path: &Path) -> anyhow::Error {
+    let dir = path.parent().unwrap_or(path).display();
+    match err.kind() {
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => {
+            anyhow!(
+                "The disk holding {} is full, free some space and try again",
+                dir
+            )
+        }
+        ErrorKind::ReadOnlyFilesystem => anyhow!("{} is on a read-only filesystem", dir),
+        ErrorKind::PermissionDenied => anyhow!("No permission to write to {}", dir),
+        _ => anyhow!("Could not write {}: {}", path.display(), err),
+    }
+}
+
+/// Checks up front that the library can be written. When it cannot, the
+/// session carries on read-only: browsing and searching work, caches are not
+/// updated and commands that change the library stop before starting.
+/// Returns why, if so.
+pub fn probe_library() -> Option<&'static str> {
+    let probe = library_dir().join(".write-probe");
+    let written = fs::create_dir_all(library_dir())
+        .and_then(|_| fs::write(&probe, b"probe"))
+        .and_then(|_| fs::remove_file(&probe));
+    if let Err(err) = written {
+        let _ = READ_ONLY.set(storage_error(err, &probe).to_string());
+    }
+    READ_ONLY.get().map(String::as_str)
+}
+
+pub fn read_only() -> bool {
+    READ_ONLY.get().is_some()
+}
+
+/// Fails early, before any work is done, when the library cannot be written.
+pub fn require_writable() -> Result<()> {
+    match READ_ONLY.get() {
+        Some(reason) => bail!("{}, nothing can be changed in this session", reason),
+        None => Ok(()),
+    }
+}
+
+/// Writes a cache file, which is skipped rather than failing the command when
+/// the library cannot be written.
+pub fn write_cache(path: &Path, contents: &[u8]) -> Result<()> {
+    if read_only() {
+        return Ok(());
+    }
+    if let Err(err) = write_atomic(path, contents) {
+        warn!("Skipped", "updating {}: {}", path.display(), err);
+    }
+    Ok(())
+}
+
+/// Writes through a temporary file that is synced and renamed over the target,
+/// so a crash leaves either the old or the new contents but never a torn file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if path.starts_with(library_dir()) {
+        require_writable()?;
+    }
+    write_synced(path, contents).map_err(|err| storage_error(err, path))
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    });
+    if written.is_err() {
+        // A half written file would only take up the space that ran out
+        let _ = fs::remove_file(&tmp_path);
+    }
+    written?;
+    // Persist the rename itself
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
     Ok(())
 }
 
 pub fn pdf_path(pdf_name: &str) -> Result<PathBuf> {
-    // Expand the tilde to the user's home directory
-    let base_dir = tilde("~/.bib/pdfs").to_string();
-    let mut pdfs_path = PathBuf::from(&base_dir);
+    let mut pdfs_path = library_dir().join("pdfs");
     // Make sure the directories exist
     fs::create_dir_all(&pdfs_path)?;
     // Append the PDF file name to the path
@@ -76,10 +280,20 @@ pub fn pdf_path(pdf_name: &str) -> Result<PathBuf> {
     // Return the full path as a PathBuf
     Ok(pdfs_path)
 }
+/// Path of a file inside the shared library, if one is configured.
+pub fn shared_path(name: &str) -> Result<Option<PathBuf>> {
+    let config = read_config_file()?;
+    Ok(config
+        .shared
+        .map(|dir| PathBuf::from(tilde(&dir).to_string()).join(name)))
+}
+
+pub fn shared_pdf_path(pdf_name: &str) -> Result<PathBuf> {
+    shared_path(&format!("pdfs/{}.pdf", pdf_name))?.ok_or(anyhow!("No shared library configured"))
+}
+
 pub fn vectors_path() -> Result<PathBuf> {
-    // Expand the tilde to the user's home directory
-    let base_dir = tilde("~/.bib").to_string();
-    let mut bib_path = PathBuf::from(&base_dir);
+    let mut bib_path = library_dir();
     // Make sure the directories exist
     fs::create_dir_all(&bib_path)?;
     // Append the PDF file name to the path
@@ -89,9 +303,7 @@ pub fn vectors_path() -> Result<PathBuf> {
 }
 
 pub fn papers_path() -> Result<PathBuf> {
-    // Expand the tilde to the user's home directory
-    let base_dir = tilde("~/.bib").to_string();
-    let mut bib_path = PathBuf::from(&base_dir);
+    let mut bib_path = library_dir();
     // Make sure the directories exist
     fs::create_dir_all(&bib_path)?;
     // Append the PDF file name to the path
@@ -100,19 +312,96 @@ pub fn papers_path() -> Result<PathBuf> {
     Ok(bib_path)
 }
 
+/// Directory holding the whole library.
+pub fn bib_dir() -> Result<PathBuf> {
+    let bib_path = library_dir();
+    // Make sure the directories exist
+    fs::create_dir_all(&bib_path)?;
+    Ok(bib_path)
+}
+
+fn bib_file(name: &str) -> Result<PathBuf> {
+    Ok(bib_dir()?.join(name))
+}
+
+pub fn fields_path() -> Result<PathBuf> {
+    bib_file("fields.bin")
+}
+
+pub fn queries_path() -> Result<PathBuf> {
+    bib_file("queries.bin")
+}
+
+pub fn vectors_model_path() -> Result<PathBuf> {
+    bib_file("vectors.model")
+}
+
+pub fn open_access_path() -> Result<PathBuf> {
+    bib_file("open_access.bin")
+}
+
+pub fn pages_path() -> Result<PathBuf> {
+    bib_file("pages.bin")
+}
+
+pub fn quotes_path() -> Result<PathBuf> {
+    bib_file("quotes.bin")
+}
+
+pub fn history_path() -> Result<PathBuf> {
+    bib_file("history.bin")
+}
+
+pub fn links_path() -> Result<PathBuf> {
+    bib_file("links.bin")
+}
+
+pub fn revisions_path() -> Result<PathBuf> {
+    bib_file("revisions.bin")
+}
+
+pub fn tombstones_path() -> Result<PathBuf> {
+    bib_file("tombstones.bin")
+}
+
+pub fn claims_path() -> Result<PathBuf> {
+    bib_file("claims.bin")
+}
+
+pub fn annotations_path() -> Result<PathBuf> {
+    bib_file("annotations.bin")
+}
+
+pub fn accesses_path() -> Result<PathBuf> {
+    bib_file("accesses.bin")
+}
+
+pub fn hashes_path() -> Result<PathBuf> {
+    bib_file("hashes.bin")
+}
+
+/// Reads a pdf that stays where it is, with the absolute path to link it by.
+pub fn read_linked_file(path: &str) -> Result<(PathBuf, Vec<u8>)> {
+    let path = fs::canonicalize(path)?;
+    let contents = fs::read(&path)?;
+    Ok((path, contents))
+}
+
 pub fn read_and_move_file(path: &str, paper_id: &str) -> Result<Vec<u8>> {
     // Read the contents of the file
     let mut file = File::open(path)?;
     let mut contents = Vec::new();
 
     file.read_to_end(&mut contents)?;
-    // Move the file
+    // Move the file, copying first since rename fails across filesystems
     let new_path = pdf_path(paper_id)?;
-    fs::rename(path, new_path)?;
+    write_atomic(&new_path, &contents)?;
+    fs::remove_file(path)?;
     // Return the contents
     Ok(contents)
 }
 
+/// Stays in ~/.bib whatever BIB_HOME is, so every library shares one download.
 pub fn model_dir() -> Result<PathBuf> {
     // Expand the tilde to the user's home directory
     let base_dir = tilde("~/.bib/llm").to_string();
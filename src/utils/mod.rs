@@ -1,2 +1,5 @@
+pub mod clock;
+pub mod collate;
+pub mod diff;
 pub mod fmt;
 pub mod io;